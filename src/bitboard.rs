@@ -0,0 +1,454 @@
+use crate::utils::PieceKind::*;
+use crate::utils::*;
+use crate::zobrist::PIECE_KINDS;
+use std::sync::OnceLock;
+
+/// One bit per square of a board of up to 8x8 squares. Square `row * 8 + col`
+/// is bit `row * 8 + col`, so bit 0 is the top left corner like row 0 of
+/// `Board.position`.
+pub type Bitboard = u64;
+
+/// Side of the largest board bitboards cover.
+pub const WIDTH: usize = 8;
+pub const SQUARES: usize = WIDTH * WIDTH;
+
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+const CAMEL_OFFSETS: [(i8, i8); 8] = [
+    (-3, -1),
+    (-3, 1),
+    (-1, -3),
+    (-1, 3),
+    (1, -3),
+    (1, 3),
+    (3, -1),
+    (3, 1),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+
+/// Magic multipliers for every square, found once by trying sparse random
+/// numbers. `AttackTables::new` checks that each of them still maps the
+/// blocker sets of its square without collisions.
+#[rustfmt::skip]
+const ROOK_MAGICS: [u64; SQUARES] = [
+    0x0980008011400020, 0x8340004410002000, 0x0880200090008268, 0x0080080080100004,
+    0x8100110004020800, 0x0300010004000822, 0x08801A0029000080, 0x8100050001204882,
+    0x0844800081400320, 0x0804402010004000, 0x0108802003100480, 0x5C00808010000800,
+    0x0003001801001014, 0x0002000200041008, 0x0004008108042210, 0x0105000100009042,
+    0x0400808000400021, 0xC100404010002000, 0x0060008010002088, 0x0400808008001000,
+    0x4440808008000400, 0x1002008004000280, 0x40024400300D1248, 0x0010020000408104,
+    0x0101008200204200, 0x8020002040005000, 0x4100100080802000, 0x4008006A80100280,
+    0x0021008500100800, 0x5000040080800200, 0x0010040101000200, 0x6140004200008104,
+    0x4000400020800090, 0x2020002080804000, 0x0000408202002010, 0x0080100501000820,
+    0x0004040080800800, 0xA01A800200800400, 0x0014820104009008, 0x0008004082000401,
+    0x0029804004608010, 0x0140002000818048, 0x000C410420010014, 0x0020100008008080,
+    0x8000080004008080, 0x0082008004008002, 0x1002000801020004, 0x0010848505620004,
+    0x0000801840002080, 0x2002010C80403200, 0x9000114220010300, 0x0001000820100100,
+    0x000A800401080080, 0xC001400410200801, 0x4800480102300400, 0x1000010040840200,
+    0x0002800442162101, 0x4000810010204202, 0x0400201200084082, 0x8200210004081001,
+    0x1002001004200802, 0x0005000208040001, 0x0002002701AC0822, 0x000010250184004A,
+];
+#[rustfmt::skip]
+const BISHOP_MAGICS: [u64; SQUARES] = [
+    0x4014700208012480, 0x0220014200A10001, 0x8108084040806120, 0x200E0A02001A00C0,
+    0x4442021080243000, 0x4C00901008100050, 0x0800821110404840, 0x0000120222024003,
+    0x008A08A041020200, 0x000118460C0428A0, 0x0000314B06020022, 0x0804180481080025,
+    0x0000340520004880, 0x0440309004200008, 0x2108084402A01000, 0x0328024422082200,
+    0xC21200C004310C00, 0x460840040820C400, 0x0010004A02720020, 0x000800488A044044,
+    0x0025000A9040000A, 0x0009010600822104, 0x0021040400821002, 0x0801000A19010120,
+    0x0004048210915000, 0x0310222005242C05, 0x00544800101C8010, 0x2308080000820003,
+    0x2008820004010401, 0x0650110244208800, 0x0009324401080802, 0x01008022A6010C40,
+    0x8081200804200940, 0x300C040305200240, 0x0002062200240800, 0x2801400A00042200,
+    0x14090104000202A0, 0x05B0020080081048, 0x0A10010910020880, 0xC403020222060B00,
+    0x260D301250402080, 0x0000A80802088800, 0x0001008041023000, 0x0000020202016420,
+    0x0008540502140400, 0x0201101008400B80, 0x0104104202000058, 0x0002080101000031,
+    0x4200A20120200004, 0x0004208410090400, 0x10A0182508088000, 0x1814081420880800,
+    0x1008810850340611, 0x0081482248020610, 0x0009080800940000, 0x0060010240890800,
+    0x048A920802080500, 0x26000E9204900404, 0x200002A080482200, 0xAC8018422020A808,
+    0x929A000440A50308, 0x41801040826C0110, 0x0140C01081010101, 0xC0A0012206040EA0,
+];
+
+pub fn get_square(location: Location) -> usize {
+    location.row * WIDTH + location.col
+}
+pub fn get_location(square: usize) -> Location {
+    Location {
+        row: square / WIDTH,
+        col: square % WIDTH,
+    }
+}
+pub fn get_bit(location: Location) -> Bitboard {
+    1 << get_square(location)
+}
+/// The squares of a `size` x `size` board.
+pub fn get_board_mask(size: usize) -> Bitboard {
+    let row = (1u64 << size) - 1;
+    (0..size).fold(0, |mask, index| mask | row << (index * WIDTH))
+}
+
+/// Iterates over the squares of a bitboard from the lowest bit up, which is
+/// the order `Board` scans its rows and columns in.
+pub struct Squares(Bitboard);
+
+impl Iterator for Squares {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        if self.0 == 0 {
+            return None;
+        }
+        let square = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+}
+
+pub fn get_squares(bitboard: Bitboard) -> Squares {
+    Squares(bitboard)
+}
+
+/// Piece placement as one bitboard per piece kind and one per color.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bitboards {
+    pieces: [Bitboard; PIECE_KINDS],
+    colors: [Bitboard; 2],
+}
+
+impl Bitboards {
+    pub fn set(&mut self, piece: Piece, location: Location) {
+        let bit = get_bit(location);
+        self.pieces[piece.kind as usize] |= bit;
+        self.colors[color_index(piece.color)] |= bit;
+    }
+    pub fn clear(&mut self, piece: Piece, location: Location) {
+        let bit = !get_bit(location);
+        self.pieces[piece.kind as usize] &= bit;
+        self.colors[color_index(piece.color)] &= bit;
+    }
+    pub fn get_occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+    pub fn get_color(&self, color: PieceColor) -> Bitboard {
+        self.colors[color_index(color)]
+    }
+    pub fn get_pieces(&self, kind: PieceKind, color: PieceColor) -> Bitboard {
+        self.pieces[kind as usize] & self.colors[color_index(color)]
+    }
+    /// The pieces of `color` that attack `square`.
+    pub fn get_attackers(&self, square: usize, color: PieceColor) -> Bitboard {
+        let tables = get_tables();
+        let occupied = self.get_occupied();
+        let queens = self.get_kinds(&[Queen, Amazon]);
+        let rooks = self.get_kinds(&[Rook, Chancellor]) | queens;
+        let bishops = self.get_kinds(&[Bishop, Archbishop]) | queens;
+        let knights = self.get_kinds(&[Knight, Archbishop, Chancellor, Amazon]);
+        // A pawn of `color` attacks `square` exactly when a pawn of the other
+        // color on `square` would attack the pawn.
+        let mut attackers = tables.pawn(opposite_color(color), square) & self.pieces[Pawn as usize]
+            | tables.knight(square) & knights
+            | tables.camel(square) & self.pieces[Camel as usize]
+            | tables.king(square) & self.pieces[King as usize]
+            | tables.rook(square, occupied) & rooks
+            | tables.bishop(square, occupied) & bishops;
+        for hopper in get_squares(self.get_pieces(Grasshopper, color)) {
+            if self.get_hops(hopper) & 1 << square != 0 {
+                attackers |= 1 << hopper;
+            }
+        }
+        attackers & self.get_color(color)
+    }
+    fn get_kinds(&self, kinds: &[PieceKind]) -> Bitboard {
+        kinds
+            .iter()
+            .fold(0, |pieces, &kind| pieces | self.pieces[kind as usize])
+    }
+    /// The squares right behind the first piece on each queen line from
+    /// `square`, where a grasshopper there lands.
+    fn get_hops(&self, square: usize) -> Bitboard {
+        let tables = get_tables();
+        let occupied = self.get_occupied();
+        let hurdles = (tables.rook(square, occupied) | tables.bishop(square, occupied)) & occupied;
+        let mut hops = 0;
+        for hurdle in get_squares(hurdles) {
+            let dy = ((hurdle / WIDTH) as i8 - (square / WIDTH) as i8).signum();
+            let dx = ((hurdle % WIDTH) as i8 - (square % WIDTH) as i8).signum();
+            if let Some(landing) = get_offset_square(hurdle, dx, dy) {
+                hops |= 1 << landing;
+            }
+        }
+        hops
+    }
+    /// The squares a piece attacks from `square`, whether or not they hold a
+    /// piece of its own color.
+    pub fn get_piece_attacks(&self, piece: Piece, square: usize) -> Bitboard {
+        let tables = get_tables();
+        let occupied = self.get_occupied();
+        match piece.kind {
+            Pawn => tables.pawn(piece.color, square),
+            Rook => tables.rook(square, occupied),
+            Knight => tables.knight(square),
+            Bishop => tables.bishop(square, occupied),
+            Queen => tables.rook(square, occupied) | tables.bishop(square, occupied),
+            King => tables.king(square),
+            Archbishop => tables.bishop(square, occupied) | tables.knight(square),
+            Chancellor => tables.rook(square, occupied) | tables.knight(square),
+            Amazon => {
+                tables.rook(square, occupied)
+                    | tables.bishop(square, occupied)
+                    | tables.knight(square)
+            }
+            Camel => tables.camel(square),
+            Grasshopper => self.get_hops(square),
+        }
+    }
+}
+
+/// A magic multiplier that maps every blocker subset of `mask` to its own
+/// slot of the sliding attack table, starting at `offset`.
+#[derive(Debug, Clone, Copy, Default)]
+struct Magic {
+    mask: Bitboard,
+    magic: u64,
+    shift: u32,
+    offset: usize,
+}
+
+impl Magic {
+    fn get_index(&self, occupied: Bitboard) -> usize {
+        self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+    }
+}
+
+pub struct AttackTables {
+    knight: [Bitboard; SQUARES],
+    camel: [Bitboard; SQUARES],
+    king: [Bitboard; SQUARES],
+    pawn: [[Bitboard; SQUARES]; 2],
+    rook: [Magic; SQUARES],
+    bishop: [Magic; SQUARES],
+    sliding: Vec<Bitboard>,
+}
+
+impl AttackTables {
+    fn new() -> Self {
+        let mut tables = AttackTables {
+            knight: std::array::from_fn(|square| get_step_attacks(square, &KNIGHT_OFFSETS)),
+            camel: std::array::from_fn(|square| get_step_attacks(square, &CAMEL_OFFSETS)),
+            king: std::array::from_fn(|square| get_step_attacks(square, &KING_OFFSETS)),
+            pawn: [
+                std::array::from_fn(|square| get_step_attacks(square, &[(-1, -1), (-1, 1)])),
+                std::array::from_fn(|square| get_step_attacks(square, &[(1, -1), (1, 1)])),
+            ],
+            rook: [Magic::default(); SQUARES],
+            bishop: [Magic::default(); SQUARES],
+            sliding: Vec::new(),
+        };
+        for square in 0..SQUARES {
+            tables.rook[square] = fill_magic_table(
+                square,
+                &ROOK_DIRECTIONS,
+                ROOK_MAGICS[square],
+                &mut tables.sliding,
+            );
+            tables.bishop[square] = fill_magic_table(
+                square,
+                &BISHOP_DIRECTIONS,
+                BISHOP_MAGICS[square],
+                &mut tables.sliding,
+            );
+        }
+        tables
+    }
+    pub fn knight(&self, square: usize) -> Bitboard {
+        self.knight[square]
+    }
+    pub fn camel(&self, square: usize) -> Bitboard {
+        self.camel[square]
+    }
+    pub fn king(&self, square: usize) -> Bitboard {
+        self.king[square]
+    }
+    /// The squares a pawn of `color` on `square` attacks.
+    pub fn pawn(&self, color: PieceColor, square: usize) -> Bitboard {
+        self.pawn[color_index(color)][square]
+    }
+    pub fn rook(&self, square: usize, occupied: Bitboard) -> Bitboard {
+        self.sliding[self.rook[square].get_index(occupied)]
+    }
+    pub fn bishop(&self, square: usize, occupied: Bitboard) -> Bitboard {
+        self.sliding[self.bishop[square].get_index(occupied)]
+    }
+}
+
+pub fn get_tables() -> &'static AttackTables {
+    static TABLES: OnceLock<AttackTables> = OnceLock::new();
+    TABLES.get_or_init(AttackTables::new)
+}
+
+fn get_offset_square(square: usize, dx: i8, dy: i8) -> Option<usize> {
+    let row = (square / WIDTH) as i8 + dy;
+    let col = (square % WIDTH) as i8 + dx;
+    if (0..WIDTH as i8).contains(&row) && (0..WIDTH as i8).contains(&col) {
+        Some(row as usize * WIDTH + col as usize)
+    } else {
+        None
+    }
+}
+
+fn get_step_attacks(square: usize, offsets: &[(i8, i8)]) -> Bitboard {
+    offsets
+        .iter()
+        .filter_map(|&(dy, dx)| get_offset_square(square, dx, dy))
+        .fold(0, |attacks, target| attacks | 1 << target)
+}
+
+/// Walks every ray from `square` up to and including the first blocker.
+fn get_ray_attacks(square: usize, directions: &[(i8, i8)], occupied: Bitboard) -> Bitboard {
+    let mut attacks = 0;
+    for &(dy, dx) in directions {
+        let mut current = square;
+        while let Some(target) = get_offset_square(current, dx, dy) {
+            attacks |= 1 << target;
+            if occupied & 1 << target != 0 {
+                break;
+            }
+            current = target;
+        }
+    }
+    attacks
+}
+
+/// The squares whose occupancy changes the attacks from `square`: every ray
+/// square except the last one before the edge.
+fn get_relevant_mask(square: usize, directions: &[(i8, i8)]) -> Bitboard {
+    let mut mask = 0;
+    for &(dy, dx) in directions {
+        let mut current = square;
+        while let Some(target) = get_offset_square(current, dx, dy) {
+            if get_offset_square(target, dx, dy).is_none() {
+                break;
+            }
+            mask |= 1 << target;
+            current = target;
+        }
+    }
+    mask
+}
+
+/// Appends the attack table of `square` for `magic` to `sliding`.
+fn fill_magic_table(
+    square: usize,
+    directions: &[(i8, i8)],
+    magic: u64,
+    sliding: &mut Vec<Bitboard>,
+) -> Magic {
+    let mask = get_relevant_mask(square, directions);
+    let bits = mask.count_ones();
+    let magic = Magic {
+        mask,
+        magic,
+        shift: 64 - bits,
+        offset: sliding.len(),
+    };
+    sliding.resize(sliding.len() + (1 << bits), 0);
+
+    // Every subset of the mask, enumerated with the carry-rippler trick.
+    let mut subset: Bitboard = 0;
+    loop {
+        let attacks = get_ray_attacks(square, directions, subset);
+        let index = magic.get_index(subset);
+        assert!(
+            sliding[index] == 0 || sliding[index] == attacks,
+            "magic collision on square {}",
+            square
+        );
+        sliding[index] = attacks;
+        subset = subset.wrapping_sub(mask) & mask;
+        if subset == 0 {
+            break;
+        }
+    }
+    magic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::PieceColor::*;
+    use crate::zobrist::Random;
+
+    #[test]
+    fn sliding_attacks_match_ray_walks() {
+        let tables = get_tables();
+        let mut random = Random(0x1234_5678_9ABC_DEF1);
+        for square in 0..SQUARES {
+            for _ in 0..64 {
+                let occupied = random.next() & random.next();
+                assert_eq!(
+                    tables.rook(square, occupied),
+                    get_ray_attacks(square, &ROOK_DIRECTIONS, occupied)
+                );
+                assert_eq!(
+                    tables.bishop(square, occupied),
+                    get_ray_attacks(square, &BISHOP_DIRECTIONS, occupied)
+                );
+            }
+        }
+    }
+    #[test]
+    fn step_attacks() {
+        let tables = get_tables();
+        let a8 = get_square(Location { row: 0, col: 0 });
+        assert_eq!(tables.knight(a8).count_ones(), 2);
+        assert_eq!(tables.king(a8).count_ones(), 3);
+        assert_eq!(tables.camel(a8).count_ones(), 2);
+        let e4 = get_square(Location { row: 4, col: 4 });
+        assert_eq!(tables.knight(e4).count_ones(), 8);
+        // First moves toward row 0.
+        let attacks: Vec<usize> = get_squares(tables.pawn(First, e4)).collect();
+        assert_eq!(attacks, vec![3 * WIDTH + 3, 3 * WIDTH + 5]);
+    }
+    #[test]
+    fn attackers_match_board_scan() {
+        for fen in [
+            "r1a1k2r/1p3p2/2z2l2/3G4/4g3/2L2c2/1P3P2/R1C1K1ZR w KQkq - 0 1",
+            "4g2k/8/8/8/8/8/R7/4K3 w - - 0 1",
+            "gzcalkn1/8/8/8/8/8/8/GZCALKN1 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let bitboards = board.get_bitboards().unwrap();
+            for square in get_squares(get_board_mask(board.size)) {
+                for color in [First, Second] {
+                    let expected: Bitboard = board
+                        .get_attackers(get_location(square), color, &[])
+                        .into_iter()
+                        .fold(0, |attackers, location| attackers | get_bit(location));
+                    assert_eq!(bitboards.get_attackers(square, color), expected, "{}", fen);
+                }
+            }
+        }
+    }
+    #[test]
+    fn board_mask() {
+        assert_eq!(get_board_mask(8), u64::MAX);
+        assert_eq!(get_board_mask(2), 0b11 | 0b11 << WIDTH);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::utils::PieceKind::*;
+use crate::utils::*;
+
+pub const CHESS960_POSITIONS: usize = 960;
+/// The index of the standard starting position.
+pub const STANDARD_INDEX: usize = 518;
+
+/// Where the two knights go among the five squares left after the bishops
+/// and the queen, by the knight digit of the index.
+const KNIGHT_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// The back rank of Chess960 position `index` in the standard numbering,
+/// from the a file to the h file.
+pub fn get_chess960_back_rank(index: usize) -> Option<[PieceKind; 8]> {
+    if index >= CHESS960_POSITIONS {
+        return None;
+    }
+    let mut rank = [None; 8];
+    let mut index = index;
+
+    // Light squared bishop on b, d, f or h, dark squared one on a, c, e or g.
+    rank[index % 4 * 2 + 1] = Some(Bishop);
+    index /= 4;
+    rank[index % 4 * 2] = Some(Bishop);
+    index /= 4;
+
+    let place = |rank: &mut [Option<PieceKind>; 8], nth: usize, kind: PieceKind| {
+        let col = (0..8).filter(|&col| rank[col].is_none()).nth(nth).unwrap();
+        rank[col] = Some(kind);
+    };
+    place(&mut rank, index % 6, Queen);
+    index /= 6;
+    let (first, second) = KNIGHT_PLACEMENTS[index];
+    // The second knight counts the empty squares left after the first one.
+    place(&mut rank, first, Knight);
+    place(&mut rank, second - 1, Knight);
+    // The king always stands between the two rooks.
+    for kind in [Rook, King, Rook] {
+        place(&mut rank, 0, kind);
+    }
+    Some(rank.map(|kind| kind.unwrap()))
+}
+
+/// The FEN of Chess960 position `index`, with X-FEN castling rights.
+pub fn get_chess960_fen(index: usize) -> Option<String> {
+    let rank: String = get_chess960_back_rank(index)?
+        .iter()
+        .map(|&kind| get_piece_kind_letter(kind))
+        .collect();
+    Some(format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+        rank,
+        rank.to_uppercase()
+    ))
+}
+
+impl Board {
+    /// A board set up for Chess960 position `index`.
+    pub fn new_chess960(index: usize) -> Option<Board> {
+        let mut board = Board::from_fen(&get_chess960_fen(index)?).ok()?;
+        board.chess960 = true;
+        Some(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perft::perft;
+    use crate::utils::PieceColor::*;
+
+    #[test]
+    fn numbering() {
+        assert_eq!(
+            get_chess960_fen(STANDARD_INDEX).unwrap(),
+            START_FEN.to_string()
+        );
+        assert_eq!(
+            get_chess960_fen(0).unwrap(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+        );
+        assert_eq!(
+            get_chess960_fen(959).unwrap(),
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB w KQkq - 0 1"
+        );
+        assert!(get_chess960_back_rank(960).is_none());
+        for index in 0..CHESS960_POSITIONS {
+            let board = Board::new_chess960(index).unwrap();
+            let king = board.get_location_from_piece(Piece::new(King, First));
+            let (short, long) = (board.castling.short[0], board.castling.long[0]);
+            assert!(long.unwrap() < king.unwrap().col && king.unwrap().col < short.unwrap());
+            assert_eq!(board.castling.short[1], short);
+            assert_eq!(board.castling.long[1], long);
+        }
+    }
+    #[test]
+    fn shredder_and_x_fen_castling() {
+        let fen = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+        let board = Board::from_fen(fen).unwrap();
+        assert!(board.chess960);
+        assert_eq!(board.castling.short, [Some(7), Some(7)]);
+        assert_eq!(board.castling.long, [Some(5), Some(5)]);
+        assert_eq!(
+            board.get_fen(),
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w KQkq - 2 9"
+        );
+        // With a second rook on the same side only the file tells them apart.
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/RR2K3 w B - 0 1").unwrap();
+        assert_eq!(board.get_fen(), "4k3/8/8/8/8/8/8/RR2K3 w B - 0 1");
+    }
+    #[test]
+    fn castling_lands_on_the_standard_squares() {
+        let mut board = Board::from_fen("1r2k2r/8/8/8/8/8/8/1R2K2R w BHbh - 0 1").unwrap();
+        let action = board.get_action_from_uci("e1b1").unwrap();
+        assert_eq!(action.kind, ActionKind::Castling(CastlingKind::Long));
+        board.commit_move(action).unwrap();
+        assert_eq!(board.get_fen(), "1r2k2r/8/8/8/8/8/8/2KR3R b kq - 1 1");
+        board.unmake_move();
+        assert_eq!(board.get_fen(), "1r2k2r/8/8/8/8/8/8/1R2K2R w KQkq - 0 1");
+        // Only the rook named by the castling file takes part.
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/5RKR w F - 0 1").unwrap();
+        let action = board.get_action_from_uci("g1f1").unwrap();
+        board.commit_move(action).unwrap();
+        assert_eq!(board.get_fen(), "4k3/8/8/8/8/8/8/2KR3R b - - 1 1");
+        // The king may already stand on its castled square.
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/6KR w H - 0 1").unwrap();
+        let action = board.get_action_from_uci("g1h1").unwrap();
+        board.commit_move(action).unwrap();
+        assert_eq!(board.get_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+    }
+    #[test]
+    fn perft_chess960() {
+        let positions: [(&str, &[u64]); 3] = [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                &[21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                &[21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                &[20, 479, 10471],
+            ),
+        ];
+        for (fen, counts) in positions {
+            let mut board = Board::from_fen(fen).unwrap();
+            for (depth, &expected) in counts.iter().enumerate() {
+                assert_eq!(perft(&mut board, depth + 1), expected, "{}", fen);
+            }
+        }
+    }
+}
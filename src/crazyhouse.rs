@@ -0,0 +1,212 @@
+use crate::utils::ActionKind::*;
+use crate::utils::PieceColor::*;
+use crate::utils::PieceKind::*;
+use crate::utils::*;
+use crate::zobrist::*;
+
+/// The kinds a hand can hold, most valuable first, in the order FEN lists
+/// them and the pockets show them.
+pub const HAND_KINDS: [PieceKind; 10] = [
+    Amazon,
+    Queen,
+    Chancellor,
+    Archbishop,
+    Rook,
+    Bishop,
+    Knight,
+    Camel,
+    Grasshopper,
+    Pawn,
+];
+
+/// The kind a captured piece goes into the hand as. Promoted pieces turn
+/// back into pawns.
+pub fn get_hand_kind(piece: Piece) -> PieceKind {
+    if piece.promoted {
+        Pawn
+    } else {
+        piece.kind
+    }
+}
+
+/// Reads the bracketed hand of a crazyhouse FEN, such as `Qp`, into piece
+/// counts: upper case letters for `First`, lower case for `Second`.
+pub fn parse_hands(hand: &str) -> Result<[[u8; PIECE_KINDS]; 2], FenError> {
+    let mut hands = [[0u8; PIECE_KINDS]; 2];
+    for letter in hand.chars() {
+        let kind = match get_piece_kind_from_letter(letter) {
+            Some(kind) if kind != King => kind,
+            _ => return Err(FenError::InvalidHand),
+        };
+        let color = if letter.is_lowercase() { Second } else { First };
+        let count = &mut hands[color_index(color)][kind as usize];
+        *count = count.checked_add(1).ok_or(FenError::InvalidHand)?;
+    }
+    Ok(hands)
+}
+
+impl Board {
+    pub fn get_hand_count(&self, color: PieceColor, kind: PieceKind) -> u8 {
+        self.hands[color_index(color)][kind as usize]
+    }
+    pub(crate) fn add_to_hand(&mut self, color: PieceColor, kind: PieceKind) {
+        let count = &mut self.hands[color_index(color)][kind as usize];
+        *count += 1;
+        self.hash ^= get_keys().hand(color, kind, *count);
+    }
+    pub(crate) fn remove_from_hand(&mut self, color: PieceColor, kind: PieceKind) {
+        let count = &mut self.hands[color_index(color)][kind as usize];
+        self.hash ^= get_keys().hand(color, kind, *count);
+        *count -= 1;
+    }
+    /// Both hands as FEN letters, the pieces of `First` before those of
+    /// `Second`.
+    pub fn get_hand_fen(&self) -> String {
+        let mut fen = String::new();
+        for color in [First, Second] {
+            for kind in HAND_KINDS {
+                let letter = get_piece_kind_letter(kind);
+                let letter = if color == First {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                };
+                for _ in 0..self.get_hand_count(color, kind) {
+                    fen.push(letter);
+                }
+            }
+        }
+        fen
+    }
+    /// Whether the side to move may drop a `kind` from its hand on `end`:
+    /// the square has to be empty, and pawns stay off the first and last
+    /// ranks.
+    pub fn is_valid_drop(&self, kind: PieceKind, end: Location) -> bool {
+        if self.get_hand_count(self.turn, kind) == 0 {
+            return false;
+        }
+        if self.get_piece_from_location(end).is_some() {
+            return false;
+        }
+        !(kind == Pawn && (end.row == 0 || end.row == self.size - 1))
+    }
+    /// The drops of the side to move that `allows` accepts, one for every
+    /// kind in hand on every square it may go to.
+    pub(crate) fn generate_drops(
+        &self,
+        allows: impl Fn(Location) -> bool,
+        actions: &mut Vec<Action>,
+    ) {
+        for kind in HAND_KINDS {
+            if self.get_hand_count(self.turn, kind) == 0 {
+                continue;
+            }
+            for row in 0..self.size {
+                for col in 0..self.size {
+                    let end = Location { row, col };
+                    if self.is_valid_drop(kind, end) && allows(end) {
+                        actions.push(Action {
+                            start: end,
+                            end,
+                            kind: Drop(kind),
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation::*;
+    use crate::perft::perft;
+    use crate::variant::*;
+
+    fn play(board: &mut Board, uci: &str) {
+        let action = board.get_action_from_uci(uci).unwrap();
+        board.commit_move(action).unwrap();
+    }
+
+    #[test]
+    fn captures_go_to_the_hand() {
+        let fen = "4k3/8/8/3p4/4P3/8/8/4K3[] w - - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.variant, Variant::Crazyhouse);
+        assert_eq!(board.get_fen(), fen);
+        let hash = board.hash();
+        play(&mut board, "e4d5");
+        assert_eq!(board.get_hand_count(First, Pawn), 1);
+        assert_eq!(board.get_fen(), "4k3/8/8/3P4/8/8/8/4K3[P] b - - 0 1");
+        play(&mut board, "e8e7");
+        play(&mut board, "P@e6");
+        assert_eq!(board.get_fen(), "8/4k3/4P3/3P4/8/8/8/4K3[] b - - 0 2");
+        assert_eq!(board.hash(), board.compute_hash());
+        board.unmake_move();
+        board.unmake_move();
+        board.unmake_move();
+        assert_eq!(board.get_fen(), fen);
+        assert_eq!(board.hash(), hash);
+    }
+    #[test]
+    fn promoted_pieces_return_as_pawns() {
+        let fen = "4k3/8/8/8/8/8/4K3/Q~6r[Rn] b - - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.get_fen(), fen);
+        play(&mut board, "h1a1");
+        assert_eq!(board.get_fen(), "4k3/8/8/8/8/8/4K3/r7[Rnp] w - - 0 2");
+        assert!(Board::from_fen("8/8/8/8/8/8/8/~K6k[] w - - 0 1").is_err());
+        assert!(Board::from_fen("8/8/8/8/8/8/8/K6k[Kx] w - - 0 1").is_err());
+        let fen = format!("8/8/8/8/8/8/8/K6k[{}] w - - 0 1", "P".repeat(256));
+        assert_eq!(Board::from_fen(&fen).err(), Some(FenError::InvalidHand));
+        let fen = format!("8/8/8/8/8/8/8/K6k[{}] w - - 0 1", "P".repeat(255));
+        assert_eq!(
+            Board::from_fen(&fen).unwrap().get_hand_count(First, Pawn),
+            255
+        );
+    }
+    #[test]
+    fn drop_rules() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K3[Pn] w - - 0 1").unwrap();
+        // A pawn may go on any of the 48 empty squares off the back ranks.
+        assert_eq!(perft(&mut board, 1), 48 + 5);
+        for uci in ["P@e1", "P@a8", "N@e2", "P@e8"] {
+            let action = board.get_action_from_uci(uci).unwrap();
+            assert!(board.test_move(action).is_err(), "{}", uci);
+        }
+        // Drops block checks but never come out of a double check.
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/r3K3[Q] w - - 0 1").unwrap();
+        let drops: Vec<String> = board
+            .get_all_valid_actions()
+            .into_iter()
+            .filter(|action| matches!(action.kind, Drop(_)))
+            .map(|action| action_to_uci(action, 8))
+            .collect();
+        assert_eq!(drops, ["Q@b1", "Q@c1", "Q@d1"]);
+        let mut board = Board::from_fen("4k3/8/8/8/8/5n2/8/r3K3[Q] w - - 0 1").unwrap();
+        assert!(board
+            .get_all_valid_actions()
+            .iter()
+            .all(|action| !matches!(action.kind, Drop(_))));
+    }
+    #[test]
+    fn drop_notation() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/8/4K3[N] w - - 0 1").unwrap();
+        let action = board.get_action_from_uci("N@d6").unwrap();
+        assert_eq!(action_to_san(&mut board, action), "N@d6+");
+        assert_eq!(san_to_action(&mut board, "N@d6+"), Ok(action));
+        assert_eq!(action_to_uci(action, 8), "N@d6");
+        assert!(board.get_action_from_uci("X@d6").is_err());
+    }
+    #[test]
+    fn perft_crazyhouse() {
+        // Five king moves, four pieces on 62 squares and pawns on 48.
+        let mut board = Board::from_fen("2k5/8/8/8/8/8/8/4K3[QRBNPqrbnp] w - - 0 1").unwrap();
+        assert_eq!(perft(&mut board, 1), 5 + 4 * 62 + 48);
+        // Nothing can be captured, let alone dropped, within three plies.
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        board.set_variant(Variant::Crazyhouse);
+        assert_eq!(perft(&mut board, 3), 8902);
+    }
+}
@@ -1,45 +1,209 @@
+use crate::eval::*;
+use crate::score::*;
+use crate::tt::*;
 use crate::utils::PieceColor::*;
+use crate::utils::PieceKind::*;
 use crate::utils::*;
+use crate::variant::*;
 use rand::seq::SliceRandom;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+pub const MAX_DEPTH: usize = 64;
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// How often, in nodes, the search looks at the clock.
+const CLOCK_CHECK_INTERVAL: u64 = 1024;
+/// How far, in centipawns, a capture may fall short of alpha before
+/// quiescence search stops looking at it.
+const DELTA_MARGIN: Score = 200;
+const KILLER_SLOTS: usize = 2;
+const HASH_ACTION_ORDER: i64 = 3_000_000_000;
+const CAPTURE_ORDER: i64 = 2_000_000_000;
+const KILLER_ORDER: i64 = 1_000_000_000;
 
 pub struct Player {
     pub kind: PlayerKind,
     pub depth: usize,
+    pub hash_size: usize,
+    pub move_time: Option<Duration>,
+    pub clock: Option<Clock>,
+    tt: TranspositionTable,
+    evaluator: Box<dyn Evaluator>,
 }
 pub enum PlayerKind {
     Human,
     Random,
     Minimax,
     Pruning,
+    Iterative,
+}
+/// The time left on a player's clock.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Clock {
+    pub remaining: Duration,
+    pub increment: Duration,
+    pub moves_to_go: Option<u32>,
+}
+/// When an iterative deepening search has to stop. A search with no limits
+/// at all runs until `MAX_DEPTH` or until it is stopped from outside.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    pub move_time: Option<Duration>,
+    pub clock: Option<Clock>,
+}
+/// The result of a completed iteration. Its value is from `First`'s point
+/// of view, with mates counted from the root.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SearchInfo {
+    pub depth: usize,
+    pub action: Action,
+    pub value: Score,
+    pub nodes: u64,
+    pub elapsed: Duration,
 }
 impl Player {
     pub fn new(kind: PlayerKind, depth: usize) -> Self {
-        Player { kind, depth }
+        Player {
+            kind,
+            depth,
+            hash_size: DEFAULT_HASH_SIZE,
+            move_time: None,
+            clock: None,
+            tt: TranspositionTable::new(DEFAULT_HASH_SIZE),
+            evaluator: Box::new(ClassicalEvaluator::default()),
+        }
+    }
+    /// Sets the size of the transposition table in megabytes.
+    pub fn with_hash_size(mut self, hash_size: usize) -> Self {
+        self.hash_size = hash_size;
+        self.tt = TranspositionTable::new(hash_size);
+        self
+    }
+    /// Gives an `Iterative` player a fixed time for every move.
+    pub fn with_move_time(mut self, move_time: Duration) -> Self {
+        self.move_time = Some(move_time);
+        self
+    }
+    /// Lets an `Iterative` player budget its time from a clock.
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = Some(clock);
+        self
     }
-    pub fn get_action(&self, board: &mut Board) -> Action {
+    /// Scores the positions the searching players reach with `evaluator`
+    /// instead of the classical evaluation.
+    pub fn with_evaluator(mut self, evaluator: impl Evaluator + 'static) -> Self {
+        self.evaluator = Box::new(evaluator);
+        self
+    }
+    pub fn get_limits(&self) -> SearchLimits {
+        SearchLimits {
+            depth: if self.depth > 0 {
+                Some(self.depth)
+            } else {
+                None
+            },
+            move_time: self.move_time,
+            clock: self.clock,
+        }
+    }
+    pub fn get_action(&mut self, board: &mut Board) -> Action {
         match self.kind {
             PlayerKind::Random => board.get_random_action(),
-            PlayerKind::Minimax => get_minimax_action(board, self.depth),
-            PlayerKind::Pruning => get_alpha_beta_action(board, self.depth),
+            PlayerKind::Minimax => get_minimax_action(board, self.depth, self.evaluator.as_mut()),
+            PlayerKind::Pruning => {
+                let stop = AtomicBool::new(false);
+                match search_alpha_beta(
+                    board,
+                    self.depth,
+                    &mut self.tt,
+                    self.evaluator.as_mut(),
+                    &stop,
+                ) {
+                    Some(info) => {
+                        println!("{} ({} nodes)", format_score(info.value), info.nodes);
+                        info.action
+                    }
+                    None => panic!("No valid action found"),
+                }
+            }
+            PlayerKind::Iterative => {
+                let stop = AtomicBool::new(false);
+                let limits = self.get_limits();
+                match search_iterative(
+                    board,
+                    &limits,
+                    &mut self.tt,
+                    self.evaluator.as_mut(),
+                    &stop,
+                    |_| {},
+                ) {
+                    Some(info) => {
+                        println!(
+                            "{} (depth {}, {} nodes)",
+                            format_score(info.value),
+                            info.depth,
+                            info.nodes
+                        );
+                        info.action
+                    }
+                    None => panic!("No valid action found"),
+                }
+            }
             _ => panic!("Human wants move"),
         }
     }
 }
 
-pub fn get_minimax_actions(board: &Board, depth: usize) -> Vec<Action> {
+impl Clock {
+    /// The time to spend on the next move: an even share of what is left
+    /// for the moves until the next time control, plus most of the
+    /// increment, but never more than half of the remaining time.
+    pub fn get_budget(&self) -> Duration {
+        let moves_to_go = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let budget = self.remaining / moves_to_go + self.increment * 3 / 4;
+        budget.min(self.remaining / 2)
+    }
+}
+
+impl SearchLimits {
+    /// Returns the soft limit, after which no new iteration is started, and
+    /// the hard limit, at which a running iteration is abandoned.
+    fn get_time_limits(&self) -> (Option<Duration>, Option<Duration>) {
+        if let Some(move_time) = self.move_time {
+            return (Some(move_time), Some(move_time));
+        }
+        match self.clock {
+            // An iteration usually takes longer than all earlier ones
+            // together, so one that starts past half the budget will not end
+            // in time.
+            Some(clock) => {
+                let budget = clock.get_budget();
+                (Some(budget / 2), Some(budget))
+            }
+            None => (None, None),
+        }
+    }
+}
+
+pub fn get_minimax_actions<E: Evaluator + ?Sized>(
+    board: &Board,
+    depth: usize,
+    evaluator: &mut E,
+) -> Vec<Action> {
     let func = if board.turn == PieceColor::First {
-        f64::max
+        Score::max
     } else {
-        f64::min
+        Score::min
     };
     let mut best = if board.turn == PieceColor::First {
-        -f64::INFINITY
+        -INFINITY
     } else {
-        f64::INFINITY
+        INFINITY
     };
 
     let mut node = board.get_position_tree(depth);
-    update_tree(&mut node);
+    update_tree(&mut node, evaluator);
     let mut actions: Vec<Action> = Vec::new();
     for child in node.children.iter() {
         let prev_best = best;
@@ -55,87 +219,652 @@ pub fn get_minimax_actions(board: &Board, depth: usize) -> Vec<Action> {
     actions
 }
 
-pub fn get_minimax_action(board: &Board, depth: usize) -> Action {
-    let actions = get_minimax_actions(board, depth);
+pub fn get_minimax_action<E: Evaluator + ?Sized>(
+    board: &Board,
+    depth: usize,
+    evaluator: &mut E,
+) -> Action {
+    let actions = get_minimax_actions(board, depth, evaluator);
     *actions.choose(&mut rand::thread_rng()).unwrap()
 }
 
 pub fn get_alpha_beta_action(board: &Board, depth: usize) -> Action {
-    let mut cloned_board = board.clone();
     println!("Turn: {:?}", board.turn);
-    fn alpha_beta(board: &mut Board, depth: usize, alpha: f64, beta: f64) -> (Option<Action>, f64) {
-        let checkmate_worth = if board.turn == First {
-            -f64::INFINITY
-        } else {
-            f64::INFINITY
-        };
+    let stop = AtomicBool::new(false);
+    let mut tt = TranspositionTable::new(DEFAULT_HASH_SIZE);
+    if let Some(info) = search_alpha_beta(
+        board,
+        depth,
+        &mut tt,
+        &mut ClassicalEvaluator::default(),
+        &stop,
+    ) {
+        println!("{} ({} nodes)", format_score(info.value), info.nodes);
+        info.action
+    } else {
+        panic!("No valid action found")
+    }
+}
 
-        // println!("Depth: {}, Turn: {:?}", depth, board.turn);
-        if board.is_checkmate() {
-            return (board.last_action, checkmate_worth);
+/// The static evaluation from the point of view of the side to move.
+fn evaluate_for_turn<E: Evaluator + ?Sized>(board: &Board, evaluator: &mut E) -> Score {
+    let value = evaluator.evaluate(board);
+    if board.turn == First {
+        value
+    } else {
+        -value
+    }
+}
+
+/// The material an action wins in centipawns, or `None` if it is a quiet
+/// move.
+fn get_capture_gain(board: &Board, action: Action) -> Option<Score> {
+    let captured = match action.kind {
+        ActionKind::EnPassant => Some(Pawn),
+        _ => board
+            .get_piece_from_location(action.end)
+            .map(|piece| piece.kind),
+    };
+    let promotion = match action.kind {
+        ActionKind::Promotion(kind) => Some(kind),
+        _ => None,
+    };
+    if captured.is_none() && promotion.is_none() {
+        return None;
+    }
+    let mut gain = captured.map_or(0, get_piece_kind_score);
+    if let Some(kind) = promotion {
+        gain += get_piece_kind_score(kind) - get_piece_kind_score(Pawn);
+    }
+    Some(gain)
+}
+
+/// Orders captures by the worth of the victim first and the worth of the
+/// attacker second.
+fn get_mvv_lva(board: &Board, action: Action, gain: Score) -> i64 {
+    let attacker = board
+        .get_piece_from_location(action.start)
+        .map_or(0, |piece| get_piece_kind_score(piece.kind));
+    gain as i64 * 100 - attacker as i64
+}
+
+/// The state shared by every node of one search.
+struct Search<'a, E: Evaluator + ?Sized> {
+    tt: &'a mut TranspositionTable,
+    evaluator: &'a mut E,
+    stop: &'a AtomicBool,
+    deadline: Option<Instant>,
+    stopped: bool,
+    nodes: u64,
+    /// Quiet actions that caused a beta cutoff, by ply.
+    killers: Vec<[Option<Action>; KILLER_SLOTS]>,
+    /// How often each quiet action caused a cutoff, weighted by depth.
+    history: Vec<i64>,
+}
+
+/// History scores are indexed by color, start square and end square, so
+/// a board of `size` needs this many of them.
+fn get_history_size(size: usize) -> usize {
+    2 * size * size * size * size
+}
+fn get_history_index(size: usize, color: PieceColor, action: Action) -> usize {
+    let squares = size * size;
+    let start = action.start.row * size + action.start.col;
+    let end = action.end.row * size + action.end.col;
+    (color_index(color) * squares + start) * squares + end
+}
+
+impl<'a, E: Evaluator + ?Sized> Search<'a, E> {
+    fn new(
+        size: usize,
+        tt: &'a mut TranspositionTable,
+        evaluator: &'a mut E,
+        stop: &'a AtomicBool,
+    ) -> Self {
+        Search {
+            tt,
+            evaluator,
+            stop,
+            deadline: None,
+            stopped: false,
+            nodes: 0,
+            killers: vec![[None; KILLER_SLOTS]; MAX_DEPTH + 1],
+            history: vec![0; get_history_size(size)],
         }
-        if board.is_moveless() {
-            return (board.last_action, 0.);
+    }
+    /// Sorts `actions` so that the likeliest cutoffs come first: the hash
+    /// action, then captures by most valuable victim and least valuable
+    /// attacker, then the killers of this ply, then quiet actions by history.
+    fn order_actions(
+        &self,
+        board: &Board,
+        actions: &mut [Action],
+        hash_action: Option<Action>,
+        ply: usize,
+    ) {
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        actions.sort_by_cached_key(|&action| {
+            let order = if Some(action) == hash_action {
+                HASH_ACTION_ORDER
+            } else if let Some(gain) = get_capture_gain(board, action) {
+                CAPTURE_ORDER + get_mvv_lva(board, action, gain)
+            } else if let Some(slot) = killers.iter().position(|&k| k == Some(action)) {
+                KILLER_ORDER - slot as i64
+            } else {
+                self.history[get_history_index(board.size, board.turn, action)]
+            };
+            -order
+        });
+    }
+    /// Remembers a quiet action that caused a beta cutoff.
+    fn update_quiet_cutoff(&mut self, board: &Board, action: Action, depth: usize, ply: usize) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(action) {
+                killers[1] = killers[0];
+                killers[0] = Some(action);
+            }
         }
-        if depth == 0 {
-            return (board.last_action, board.get_material_difference());
+        self.history[get_history_index(board.size, board.turn, action)] += (depth * depth) as i64;
+    }
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped && self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        if !self.stopped && self.nodes.is_multiple_of(CLOCK_CHECK_INTERVAL) {
+            if let Some(deadline) = self.deadline {
+                self.stopped = Instant::now() >= deadline;
+            }
+        }
+        self.stopped
+    }
+    /// Negamax alpha-beta search. Values are from the point of view of the
+    /// side to move; every node is stored in the transposition table and its
+    /// move is tried first when the position comes up again.
+    fn alpha_beta(
+        &mut self,
+        board: &mut Board,
+        depth: usize,
+        ply: usize,
+        alpha: Score,
+        beta: Score,
+    ) -> (Option<Action>, Score) {
+        self.nodes += 1;
+        if self.should_stop() {
+            return (None, 0);
+        }
+        if let Some(value) = get_variant_value(board, ply) {
+            return (None, value);
         }
-
         let mut alpha = alpha;
         let mut beta = beta;
+        let original_alpha = alpha;
+        let hash = board.hash();
 
-        let mut actions: Vec<Action> = Vec::new();
-        // let mut best_action = None;
-        let mut best_value = if board.turn == First {
-            -f64::INFINITY
-        } else {
-            f64::INFINITY
-        };
-        let mut next_boards = board.get_next_boards();
-        for next_board in next_boards.iter_mut() {
-            assert_ne!(next_board.turn, board.turn);
-            let (_, value) = alpha_beta(next_board, depth - 1, alpha, beta);
-
-            if board.turn == First {
-                if value >= best_value {
-                    if value > best_value {
-                        actions = Vec::new();
-                    }
-                    // best_action = next_board.last_action;
-                    best_value = value;
-                    actions.push(next_board.last_action.unwrap());
+        let mut hash_action = None;
+        if let Some(entry) = self.tt.probe(hash) {
+            hash_action = entry.action;
+            if entry.depth >= depth {
+                let value = score_from_tt(entry.value, ply);
+                match entry.bound {
+                    Bound::Exact => return (entry.action, value),
+                    Bound::Lower => alpha = alpha.max(value),
+                    Bound::Upper => beta = beta.min(value),
                 }
-                alpha = f64::max(alpha, best_value);
-                if best_value >= beta {
-                    break;
+                if alpha >= beta {
+                    return (entry.action, value);
                 }
+            }
+        }
+
+        if depth == 0 {
+            return (None, self.quiescence(board, ply, alpha, beta));
+        }
+        let mut actions = board.get_all_valid_actions();
+        if actions.is_empty() {
+            return (None, get_moveless_value(board, ply));
+        }
+
+        self.order_actions(board, &mut actions, hash_action, ply);
+
+        let mut best_action = None;
+        let mut best_value = -INFINITY;
+        for action in actions {
+            board.make_move(action);
+            let value = if board.is_search_draw() {
+                0
             } else {
-                if value <= best_value {
-                    if value < best_value {
-                        actions = Vec::new();
-                    }
-                    // best_action = next_board.last_action;
-                    best_value = value;
-                    actions.push(next_board.last_action.unwrap());
+                -self.alpha_beta(board, depth - 1, ply + 1, -beta, -alpha).1
+            };
+            board.unmake_move();
+
+            if value > best_value || best_action.is_none() {
+                best_value = value;
+                best_action = Some(action);
+            }
+            alpha = alpha.max(best_value);
+            if alpha >= beta {
+                if get_capture_gain(board, action).is_none() {
+                    self.update_quiet_cutoff(board, action, depth, ply);
                 }
-                beta = f64::min(beta, best_value);
-                if best_value <= alpha {
-                    break;
+                break;
+            }
+        }
+
+        if self.stopped {
+            return (None, 0);
+        }
+        let bound = if best_value <= original_alpha {
+            Bound::Upper
+        } else if best_value >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        let stored = score_to_tt(best_value, ply);
+        self.tt.store(hash, depth, bound, stored, best_action);
+        (best_action, best_value)
+    }
+    /// Resolves captures and promotions at the leaves, so that the
+    /// evaluation is never taken in the middle of an exchange. The side to
+    /// move may stand pat on the static evaluation unless it is in check, in
+    /// which case every evasion is searched.
+    fn quiescence(&mut self, board: &mut Board, ply: usize, alpha: Score, beta: Score) -> Score {
+        self.nodes += 1;
+        if self.should_stop() {
+            return 0;
+        }
+        if let Some(value) = get_variant_value(board, ply) {
+            return value;
+        }
+        let mut alpha = alpha;
+        let in_check = board.is_check(board.turn);
+        let stand_pat = evaluate_for_turn(board, self.evaluator);
+        if !in_check {
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+        }
+
+        let mut actions = board.get_all_valid_actions();
+        if actions.is_empty() && (in_check || board.variant == Variant::Antichess) {
+            return get_moveless_value(board, ply);
+        }
+        // No ply ever stores killers at MAX_DEPTH, so evasions go by history.
+        self.order_actions(board, &mut actions, None, MAX_DEPTH);
+        let mut best_value = if in_check { -INFINITY } else { stand_pat };
+        for action in actions {
+            if !in_check {
+                // Underpromotions are left to the full-width search.
+                if matches!(action.kind, ActionKind::Promotion(kind) if kind != Queen) {
+                    continue;
+                }
+                let gain = match get_capture_gain(board, action) {
+                    Some(gain) => gain,
+                    None => continue,
+                };
+                // Delta pruning: even winning the piece outright would not
+                // bring the score back up to alpha.
+                if stand_pat + gain + DELTA_MARGIN < alpha {
+                    continue;
                 }
             }
+            board.make_move(action);
+            let value = -self.quiescence(board, ply + 1, -beta, -alpha);
+            board.unmake_move();
+
+            best_value = best_value.max(value);
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best_value
+    }
+    /// Searches the root to `depth` and returns the best action with its
+    /// value from `First`'s point of view, unless the search was stopped.
+    fn search_root(&mut self, board: &mut Board, depth: usize) -> Option<(Action, Score)> {
+        let (action, value) = self.alpha_beta(board, depth, 0, -INFINITY, INFINITY);
+        if self.stopped {
+            return None;
         }
-        (actions.choose(&mut rand::thread_rng()).cloned(), best_value)
-        // (best_action, best_value)
+        let value = if board.turn == First { value } else { -value };
+        action.map(|action| (action, value))
     }
+}
 
-    let alpha = -f64::INFINITY;
-    let beta = f64::INFINITY;
-    let (action, ab) = alpha_beta(&mut cloned_board, depth, alpha, beta);
+/// Runs the alpha-beta search to `depth` and returns the chosen action with
+/// its value from `First`'s point of view, or `None` if `stop` was raised
+/// before the search finished.
+pub fn search_alpha_beta<E: Evaluator + ?Sized>(
+    board: &Board,
+    depth: usize,
+    tt: &mut TranspositionTable,
+    evaluator: &mut E,
+    stop: &AtomicBool,
+) -> Option<SearchInfo> {
+    let start = Instant::now();
+    let mut cloned_board = board.clone();
+    tt.new_search();
+    let mut search = Search::new(board.size, tt, evaluator, stop);
+    let (action, value) = search.search_root(&mut cloned_board, depth)?;
+    Some(SearchInfo {
+        depth,
+        action,
+        value,
+        nodes: search.nodes,
+        elapsed: start.elapsed(),
+    })
+}
 
-    if let Some(action) = action {
-        println!("{:.2}", ab);
-        action
-    } else {
-        panic!("No valid action found")
+/// Searches one ply deeper at a time until `limits` say to stop, calling
+/// `report` after every completed iteration. Returns the result of the last
+/// completed iteration, so a move is available however early it stops.
+pub fn search_iterative<E: Evaluator + ?Sized>(
+    board: &Board,
+    limits: &SearchLimits,
+    tt: &mut TranspositionTable,
+    evaluator: &mut E,
+    stop: &AtomicBool,
+    mut report: impl FnMut(&SearchInfo),
+) -> Option<SearchInfo> {
+    let start = Instant::now();
+    let mut cloned_board = board.clone();
+    let (soft_limit, hard_limit) = limits.get_time_limits();
+    let max_depth = limits.depth.unwrap_or(MAX_DEPTH).min(MAX_DEPTH);
+
+    tt.new_search();
+    let mut search = Search::new(board.size, tt, evaluator, stop);
+    let mut best: Option<SearchInfo> = None;
+    for depth in 1..=max_depth {
+        // The first iteration always finishes, so there is a move to play.
+        if depth > 1 {
+            search.deadline = hard_limit.map(|limit| start + limit);
+        }
+        let (action, value) = match search.search_root(&mut cloned_board, depth) {
+            Some(result) => result,
+            None => break,
+        };
+        let info = SearchInfo {
+            depth,
+            action,
+            value,
+            nodes: search.nodes,
+            elapsed: start.elapsed(),
+        };
+        report(&info);
+        best = Some(info);
+
+        if is_mate(value) {
+            break;
+        }
+        if soft_limit.is_some_and(|limit| start.elapsed() >= limit) {
+            break;
+        }
+        if limits.clock.is_some() && cloned_board.count_valid_actions() == 1 {
+            break;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn best_action(fen: &str, depth: usize) -> (String, Score) {
+        let board = Board::from_fen(fen).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let info = search_alpha_beta(
+            &board,
+            depth,
+            &mut tt,
+            &mut ClassicalEvaluator::default(),
+            &stop,
+        )
+        .unwrap();
+        (action_to_uci(info.action, board.size), info.value)
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let (action, value) = best_action("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2);
+        assert_eq!(action, "a1a8");
+        assert_eq!(value, mate_in(1));
+        let (action, value) = best_action("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1", 2);
+        assert_eq!(action, "a8a1");
+        assert_eq!(value, -mate_in(1));
+    }
+    #[test]
+    fn mates_on_the_fiftieth_move() {
+        let (action, value) = best_action("7k/8/5K2/8/8/8/8/6Q1 w - - 99 80", 2);
+        assert_eq!(action, "g1g7");
+        assert_eq!(value, mate_in(1));
+    }
+    #[test]
+    fn counts_the_plies_to_mate() {
+        // 1. Kg6 Kg8 2. Ra8#, and the search stops once it has found it.
+        let board = Board::from_fen("7k/8/5K2/8/8/8/8/R7 w - - 0 1").unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let limits = SearchLimits {
+            depth: Some(5),
+            ..SearchLimits::default()
+        };
+        let mut evaluator = ClassicalEvaluator::default();
+        let info =
+            search_iterative(&board, &limits, &mut tt, &mut evaluator, &stop, |_| {}).unwrap();
+        assert_eq!(info.value, mate_in(3));
+        assert_eq!(info.depth, 3);
+        assert_eq!(format_score(info.value), "mate 2");
+        // Searching deeper than it takes still reports the mate in one.
+        let (action, value) = best_action("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3);
+        assert_eq!(action, "a1a8");
+        assert_eq!(value, mate_in(1));
+    }
+    #[test]
+    fn takes_a_hanging_queen() {
+        let (action, _) = best_action("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 2);
+        assert_eq!(action, "d2d5");
+    }
+    #[test]
+    fn transposition_table_keeps_results_between_searches() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let first = search_alpha_beta(
+            &board,
+            3,
+            &mut tt,
+            &mut ClassicalEvaluator::default(),
+            &stop,
+        )
+        .unwrap();
+        assert!(tt.probe(board.hash()).is_some());
+        let second = search_alpha_beta(
+            &board,
+            3,
+            &mut tt,
+            &mut ClassicalEvaluator::default(),
+            &stop,
+        )
+        .unwrap();
+        assert_eq!(first.action, second.action);
+        assert_eq!(first.value, second.value);
+        assert!(second.nodes < first.nodes);
+    }
+    #[test]
+    fn search_scores_leaves_with_the_given_evaluator() {
+        // Rewards the first player for every piece it still has, however
+        // little it is worth.
+        struct Counting(u64);
+        impl Evaluator for Counting {
+            fn evaluate(&mut self, board: &Board) -> Score {
+                self.0 += 1;
+                let pieces = board.position.iter().flatten().flatten();
+                pieces.filter(|piece| piece.color == First).count() as Score
+            }
+        }
+        let board = Board::from_fen("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1").unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let mut evaluator = Counting(0);
+        let info = search_alpha_beta(&board, 1, &mut tt, &mut evaluator, &stop).unwrap();
+        assert!(evaluator.0 > 0);
+        // Only taking the queen keeps both pieces out of its reach.
+        assert_eq!(action_to_uci(info.action, board.size), "d2d5");
+        assert_eq!(info.value, 2);
+    }
+    #[test]
+    fn stop_aborts_the_search() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(true);
+        assert!(search_alpha_beta(
+            &board,
+            3,
+            &mut tt,
+            &mut ClassicalEvaluator::default(),
+            &stop
+        )
+        .is_none());
+    }
+    #[test]
+    fn iterative_deepening_reports_every_depth() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..SearchLimits::default()
+        };
+        let mut depths = Vec::new();
+        let info = search_iterative(
+            &board,
+            &limits,
+            &mut tt,
+            &mut ClassicalEvaluator::default(),
+            &stop,
+            |info| depths.push(info.depth),
+        )
+        .unwrap();
+        assert_eq!(depths, vec![1, 2, 3]);
+        assert_eq!(info.depth, 3);
+    }
+    #[test]
+    fn iterative_deepening_keeps_to_the_move_time() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let limits = SearchLimits {
+            move_time: Some(Duration::from_millis(100)),
+            ..SearchLimits::default()
+        };
+        let start = Instant::now();
+        let info = search_iterative(
+            &board,
+            &limits,
+            &mut tt,
+            &mut ClassicalEvaluator::default(),
+            &stop,
+            |_| {},
+        )
+        .unwrap();
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(info.depth >= 1);
+    }
+    #[test]
+    fn clock_budget() {
+        let clock = Clock {
+            remaining: Duration::from_secs(60),
+            increment: Duration::from_secs(2),
+            moves_to_go: Some(20),
+        };
+        assert_eq!(clock.get_budget(), Duration::from_millis(4500));
+        let clock = Clock {
+            remaining: Duration::from_secs(1),
+            increment: Duration::from_secs(2),
+            moves_to_go: None,
+        };
+        assert_eq!(clock.get_budget(), Duration::from_millis(500));
+    }
+    #[test]
+    fn does_not_take_a_defended_pawn_with_the_queen() {
+        let (action, _) = best_action("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1", 1);
+        assert_ne!(action, "d2d5");
+    }
+    #[test]
+    fn quiescence_resolves_exchanges() {
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let mut evaluator = ClassicalEvaluator::default();
+        let mut search = Search::new(8, &mut tt, &mut evaluator, &stop);
+
+        // Taking the defended pawn loses the queen, so White stands pat.
+        let mut board = Board::from_fen("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let stand_pat = evaluate_for_turn(&board, search.evaluator);
+        assert_eq!(
+            search.quiescence(&mut board, 0, -INFINITY, INFINITY),
+            stand_pat
+        );
+        board.make_move(board.get_action_from_uci("d2d5").unwrap());
+        let exchange = -search.quiescence(&mut board, 1, -INFINITY, INFINITY);
+        assert!(exchange < stand_pat - 600);
+        // An undefended pawn is simply won.
+        let mut board = Board::from_fen("4k3/8/8/3p4/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let stand_pat = evaluate_for_turn(&board, search.evaluator);
+        assert!(search.quiescence(&mut board, 0, -INFINITY, INFINITY) > stand_pat + 50);
+    }
+    #[test]
+    fn quiescence_prunes_by_stand_pat_and_delta() {
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let mut evaluator = ClassicalEvaluator::default();
+        let mut search = Search::new(8, &mut tt, &mut evaluator, &stop);
+        let mut board = Board::from_fen("4k3/8/8/p2p2p1/8/8/3Q4/4K3 w - - 0 1").unwrap();
+        let stand_pat = evaluate_for_turn(&board, search.evaluator);
+        let mut count_nodes = |alpha, beta| {
+            search.nodes = 0;
+            let value = search.quiescence(&mut board, 0, alpha, beta);
+            (value, search.nodes)
+        };
+
+        let (_, full) = count_nodes(-INFINITY, INFINITY);
+        assert!(full > 3);
+        // Standing pat already beats beta.
+        assert_eq!(count_nodes(-INFINITY, stand_pat - 1), (stand_pat, 1));
+        // No pawn capture can lift the score anywhere near alpha.
+        let alpha = stand_pat + 100 + DELTA_MARGIN + 50;
+        assert_eq!(count_nodes(alpha, INFINITY), (stand_pat, 1));
+        // Closer to alpha the captures are searched again.
+        let (_, near) = count_nodes(stand_pat + 50, INFINITY);
+        assert!(near > 1);
+    }
+    #[test]
+    fn actions_are_ordered_hash_captures_killers_quiet() {
+        let board = Board::from_fen("4k3/8/8/3q4/4P3/8/3R4/4K3 w - - 0 1").unwrap();
+        let mut tt = TranspositionTable::new(1);
+        let stop = AtomicBool::new(false);
+        let mut evaluator = ClassicalEvaluator::default();
+        let mut search = Search::new(board.size, &mut tt, &mut evaluator, &stop);
+        let hash_action = board.get_action_from_uci("e1f1").unwrap();
+        let killer = board.get_action_from_uci("d2c2").unwrap();
+        search.killers[3][0] = Some(killer);
+        let mut actions: Vec<Action> = ["d2a2", "d2c2", "d2d5", "e4d5", "e1f1"]
+            .iter()
+            .map(|input| board.get_action_from_uci(input).unwrap())
+            .collect();
+        search.order_actions(&board, &mut actions, Some(hash_action), 3);
+        let ordered: Vec<String> = actions
+            .iter()
+            .map(|&action| action_to_uci(action, board.size))
+            .collect();
+        assert_eq!(ordered, ["e1f1", "e4d5", "d2d5", "d2c2", "d2a2"]);
+    }
+    #[test]
+    fn underpromotes_to_fork_king_and_queen() {
+        let (action, _) = best_action("8/5P1k/4q3/8/8/8/P7/K7 w - - 0 1", 3);
+        assert_eq!(action, "f7f8n");
     }
 }
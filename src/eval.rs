@@ -0,0 +1,599 @@
+use crate::crazyhouse::*;
+use crate::pawns::*;
+use crate::score::*;
+use crate::utils::PieceColor::*;
+use crate::utils::PieceKind::*;
+use crate::utils::*;
+use crate::variant::*;
+
+/// The game phase with all the orthodox pieces on the board. Richer
+/// setups are counted as a full midgame.
+pub const MAX_PHASE: i32 = 24;
+
+/// Attack units on a king zone are squared and scaled by this into a
+/// penalty in centipawns, up to `MAX_KING_ATTACK`.
+const KING_ATTACK_SCALE: i32 = 2;
+const MAX_KING_ATTACK: i32 = 500;
+
+/// Side of the piece-square tables. Other boards look their squares up by
+/// scaling rows and columns onto it.
+const TABLE_SIZE: usize = 8;
+
+type Table = [[i32; TABLE_SIZE]; TABLE_SIZE];
+
+// The tables are in centipawns and seen from `First`'s side: row 0 is the
+// far row, where its pawns promote. `Second` reads them upside down.
+#[rustfmt::skip]
+const PAWN_MG: Table = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 50,  50,  50,  50,  50,  50,  50,  50],
+    [ 10,  10,  20,  30,  30,  20,  10,  10],
+    [  5,   5,  10,  25,  25,  10,   5,   5],
+    [  0,   0,   0,  20,  20,   0,   0,   0],
+    [  5,  -5, -10,   0,   0, -10,  -5,   5],
+    [  5,  10,  10, -20, -20,  10,  10,   5],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+#[rustfmt::skip]
+const PAWN_EG: Table = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 80,  80,  80,  80,  80,  80,  80,  80],
+    [ 50,  50,  50,  50,  50,  50,  50,  50],
+    [ 30,  30,  30,  30,  30,  30,  30,  30],
+    [ 15,  15,  15,  15,  15,  15,  15,  15],
+    [  5,   5,   5,   5,   5,   5,   5,   5],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+#[rustfmt::skip]
+const KNIGHT_MG: Table = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20,   0,   0,   0,   0, -20, -40],
+    [-30,   0,  10,  15,  15,  10,   0, -30],
+    [-30,   5,  15,  20,  20,  15,   5, -30],
+    [-30,   0,  15,  20,  20,  15,   0, -30],
+    [-30,   5,  10,  15,  15,  10,   5, -30],
+    [-40, -20,   0,   5,   5,   0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+#[rustfmt::skip]
+const KNIGHT_EG: Table = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20,   0,   0,   0,   0, -20, -40],
+    [-30,   0,  10,  15,  15,  10,   0, -30],
+    [-30,   0,  15,  20,  20,  15,   0, -30],
+    [-30,   0,  15,  20,  20,  15,   0, -30],
+    [-30,   0,  10,  15,  15,  10,   0, -30],
+    [-40, -20,   0,   0,   0,   0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+#[rustfmt::skip]
+const BISHOP_MG: Table = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,  10,  10,   5,   0, -10],
+    [-10,   5,   5,  10,  10,   5,   5, -10],
+    [-10,   0,  10,  10,  10,  10,   0, -10],
+    [-10,  10,  10,  10,  10,  10,  10, -10],
+    [-10,   5,   0,   0,   0,   0,   5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+#[rustfmt::skip]
+const BISHOP_EG: Table = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,  10,  10,   5,   0, -10],
+    [-10,   0,  10,  15,  15,  10,   0, -10],
+    [-10,   0,  10,  15,  15,  10,   0, -10],
+    [-10,   0,   5,  10,  10,   5,   0, -10],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+#[rustfmt::skip]
+const ROOK_MG: Table = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  5,  10,  10,  10,  10,  10,  10,   5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [  0,   0,   0,   5,   5,   0,   0,   0],
+];
+#[rustfmt::skip]
+const ROOK_EG: Table = [
+    [  5,   5,   5,   5,   5,   5,   5,   5],
+    [ 10,  10,  10,  10,  10,  10,  10,  10],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+#[rustfmt::skip]
+const QUEEN_MG: Table = [
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,   5,   5,   5,   0, -10],
+    [ -5,   0,   5,   5,   5,   5,   0,  -5],
+    [  0,   0,   5,   5,   5,   5,   0,  -5],
+    [-10,   5,   5,   5,   5,   5,   0, -10],
+    [-10,   0,   5,   0,   0,   0,   0, -10],
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+];
+#[rustfmt::skip]
+const QUEEN_EG: Table = [
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+    [-10,   0,   5,   5,   5,   5,   0, -10],
+    [-10,   5,  10,  10,  10,  10,   5, -10],
+    [ -5,   5,  10,  15,  15,  10,   5,  -5],
+    [ -5,   5,  10,  15,  15,  10,   5,  -5],
+    [-10,   5,  10,  10,  10,  10,   5, -10],
+    [-10,   0,   5,   5,   5,   5,   0, -10],
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+];
+#[rustfmt::skip]
+const KING_MG: Table = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [ 20,  20,   0,   0,   0,   0,  20,  20],
+    [ 20,  30,  10,   0,   0,  10,  30,  20],
+];
+#[rustfmt::skip]
+const KING_EG: Table = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10,   0,   0, -10, -20, -30],
+    [-30, -10,  20,  30,  30,  20, -10, -30],
+    [-30, -10,  30,  40,  40,  30, -10, -30],
+    [-30, -10,  30,  40,  40,  30, -10, -30],
+    [-30, -10,  20,  30,  30,  20, -10, -30],
+    [-30, -30,   0,   0,   0,   0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
+
+/// The midgame and endgame tables of a piece. The fairy pieces borrow the
+/// tables of the orthodox piece whose moves they share the most.
+fn get_tables(kind: PieceKind) -> (&'static Table, &'static Table) {
+    match kind {
+        Pawn => (&PAWN_MG, &PAWN_EG),
+        Knight | Camel | Archbishop => (&KNIGHT_MG, &KNIGHT_EG),
+        Bishop => (&BISHOP_MG, &BISHOP_EG),
+        Rook | Chancellor => (&ROOK_MG, &ROOK_EG),
+        Queen | Amazon | Grasshopper => (&QUEEN_MG, &QUEEN_EG),
+        King => (&KING_MG, &KING_EG),
+    }
+}
+
+/// How much a piece on the board counts towards the midgame.
+pub fn get_phase_weight(kind: PieceKind) -> i32 {
+    match kind {
+        Pawn | King => 0,
+        Knight | Bishop | Camel | Grasshopper => 1,
+        Rook => 2,
+        Archbishop => 3,
+        Queen | Chancellor => 4,
+        Amazon => 5,
+    }
+}
+
+/// The midgame and endgame bonus for each square a piece attacks. Long
+/// range pieces reach many squares anyway, so each counts for less.
+fn get_mobility_weight(kind: PieceKind) -> (i32, i32) {
+    match kind {
+        Pawn | King => (0, 0),
+        Knight | Bishop | Camel => (4, 4),
+        Rook => (2, 4),
+        Queen => (1, 2),
+        Archbishop | Grasshopper => (3, 3),
+        Chancellor => (2, 3),
+        Amazon => (1, 2),
+    }
+}
+
+/// How much a piece bearing down on the enemy king counts towards the
+/// attack.
+fn get_king_attack_weight(kind: PieceKind) -> i32 {
+    match kind {
+        Pawn | King => 0,
+        Knight | Bishop | Camel | Grasshopper => 2,
+        Rook => 3,
+        Archbishop | Chancellor => 4,
+        Queen => 5,
+        Amazon => 6,
+    }
+}
+
+/// How much each of the optional evaluation terms counts, in percent of
+/// its usual strength. A weight of 0 switches the term off and skips the
+/// work it takes, so the effect of each one can be measured on its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvalWeights {
+    /// The squares each piece attacks.
+    pub mobility: i32,
+    /// The pieces attacking the squares around the enemy king.
+    pub king_attack: i32,
+    /// Files next to the king without a pawn of its own.
+    pub open_files: i32,
+    /// Holes in the row of pawns in front of the king.
+    pub pawn_shield: i32,
+}
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        EvalWeights {
+            mobility: 100,
+            king_attack: 100,
+            open_files: 100,
+            pawn_shield: 100,
+        }
+    }
+}
+
+/// Mobility and king attacks, which both come from the attack sets of the
+/// pieces and are gathered in one pass.
+#[derive(Debug, Default)]
+struct Activity {
+    /// Midgame and endgame mobility from `First`'s point of view.
+    mobility: (i32, i32),
+    /// The pieces attacking the zone around each king and their attack
+    /// units, indexed by the color of the king.
+    king_attackers: [i32; 2],
+    king_attack_units: [i32; 2],
+}
+
+fn get_activity(board: &Board, weights: &EvalWeights) -> Activity {
+    let mut activity = Activity::default();
+    let kings = [First, Second].map(|color| {
+        board
+            .get_location_from_piece(Piece::new(King, color))
+            .filter(|_| board.variant.has_royal_king() && weights.king_attack != 0)
+    });
+    for row in 0..board.size {
+        for col in 0..board.size {
+            let location = Location { row, col };
+            let piece = match board.get_piece_from_location(location) {
+                Some(piece) if piece.kind != Pawn && piece.kind != King => piece,
+                _ => continue,
+            };
+            let attacks = board.get_piece_attacks(location);
+            if weights.mobility != 0 {
+                let reachable = attacks
+                    .iter()
+                    .filter(|&&end| {
+                        board
+                            .get_piece_from_location(end)
+                            .is_none_or(|other| other.color != piece.color)
+                    })
+                    .count() as i32;
+                let (mg, eg) = get_mobility_weight(piece.kind);
+                let sign = if piece.color == First { 1 } else { -1 };
+                activity.mobility.0 += sign * mg * reachable;
+                activity.mobility.1 += sign * eg * reachable;
+            }
+            let enemy = color_index(opposite_color(piece.color));
+            if let Some(king) = kings[enemy] {
+                let in_zone = |end: &&Location| {
+                    end.row.abs_diff(king.row) <= 1 && end.col.abs_diff(king.col) <= 1
+                };
+                if attacks.iter().any(|end| in_zone(&end)) {
+                    activity.king_attackers[enemy] += 1;
+                    activity.king_attack_units[enemy] += get_king_attack_weight(piece.kind);
+                }
+            }
+        }
+    }
+    activity
+}
+
+/// The midgame penalty for the attack on `color`'s king. A lone attacker
+/// is not yet a threat.
+fn get_king_attack_penalty(activity: &Activity, color: PieceColor) -> i32 {
+    let index = color_index(color);
+    if activity.king_attackers[index] < 2 {
+        return 0;
+    }
+    let units = activity.king_attack_units[index];
+    -(units * units * KING_ATTACK_SCALE).min(MAX_KING_ATTACK)
+}
+
+/// Maps a row or column of a board of `size` onto the tables, keeping the
+/// edges on the edges and the centre in the centre.
+pub(crate) fn scale_to_table(index: usize, size: usize) -> usize {
+    (2 * index + 1) * TABLE_SIZE / (2 * size)
+}
+
+/// The midgame and endgame bonus of `piece` standing on `location`.
+pub fn get_square_bonus(piece: Piece, location: Location, size: usize) -> (i32, i32) {
+    let row = if piece.color == First {
+        location.row
+    } else {
+        size - 1 - location.row
+    };
+    let row = scale_to_table(row, size);
+    let col = scale_to_table(location.col, size);
+    let (mg, eg) = get_tables(piece.kind);
+    (mg[row][col], eg[row][col])
+}
+
+/// The worth of a piece kind in centipawns.
+pub fn get_piece_kind_score(kind: PieceKind) -> Score {
+    to_centipawns(get_piece_kind_worth(kind))
+}
+fn to_centipawns(pawns: f64) -> Score {
+    (pawns * 100.).round() as Score
+}
+
+/// The worth of a piece in centipawns. Kings are left out where they
+/// cannot be captured, as both sides always have one.
+fn get_centipawn_worth(kind: PieceKind, variant: Variant) -> i32 {
+    if kind == King && variant.has_royal_king() {
+        return 0;
+    }
+    get_piece_kind_score(kind)
+}
+
+impl Board {
+    /// How far the game is from the endgame, from `MAX_PHASE` with all
+    /// the pieces on the board down to 0 with only kings and pawns left.
+    /// Pieces in hand count, as they can come back at any moment.
+    pub fn get_phase(&self) -> i32 {
+        let mut phase = 0;
+        for piece in self.position.iter().flatten().flatten() {
+            phase += get_phase_weight(piece.kind);
+        }
+        for color in [First, Second] {
+            for kind in HAND_KINDS {
+                phase += self.get_hand_count(color, kind) as i32 * get_phase_weight(kind);
+            }
+        }
+        phase.min(MAX_PHASE)
+    }
+}
+
+/// Scores the positions at the leaves of the search. The search is generic
+/// over it, so variants and experiments can bring their own scoring.
+pub trait Evaluator {
+    /// The value of `board` in centipawns from `First`'s point of view.
+    fn evaluate(&mut self, board: &Board) -> Score;
+}
+
+/// Counts material and nothing else.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaterialEvaluator;
+
+/// Material and piece-square bonuses, blended from the midgame to the
+/// endgame tables as pieces come off.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PstEvaluator;
+
+/// Material, piece-square bonuses, pawn structure, mobility and king
+/// safety, with the pawn structure cached in a pawn hash table.
+pub struct ClassicalEvaluator {
+    pub weights: EvalWeights,
+    pawns: PawnTable,
+}
+
+impl ClassicalEvaluator {
+    pub fn new(weights: EvalWeights) -> Self {
+        ClassicalEvaluator {
+            weights,
+            pawns: PawnTable::new(DEFAULT_PAWN_HASH_SIZE),
+        }
+    }
+}
+
+impl Default for ClassicalEvaluator {
+    fn default() -> Self {
+        Self::new(EvalWeights::default())
+    }
+}
+
+// In antichess material is a burden and nothing else matters, so every
+// evaluator falls back on the negated material balance.
+
+impl Evaluator for MaterialEvaluator {
+    fn evaluate(&mut self, board: &Board) -> Score {
+        let difference = to_centipawns(board.get_material_difference());
+        if board.variant == Variant::Antichess {
+            -difference
+        } else {
+            difference
+        }
+    }
+}
+
+impl Evaluator for PstEvaluator {
+    fn evaluate(&mut self, board: &Board) -> Score {
+        if board.variant == Variant::Antichess {
+            return MaterialEvaluator.evaluate(board);
+        }
+        let (mg, eg) = get_material_and_squares(board);
+        taper(board, mg, eg)
+    }
+}
+
+impl Evaluator for ClassicalEvaluator {
+    fn evaluate(&mut self, board: &Board) -> Score {
+        if board.variant == Variant::Antichess {
+            return MaterialEvaluator.evaluate(board);
+        }
+        let pawns = self.pawns.get(board);
+        evaluate_classical(board, &self.weights, &pawns)
+    }
+}
+
+/// The full classical evaluation with the default weights, working the
+/// pawn structure out from scratch.
+pub fn evaluate(board: &Board) -> Score {
+    if board.variant == Variant::Antichess {
+        return MaterialEvaluator.evaluate(board);
+    }
+    evaluate_classical(board, &EvalWeights::default(), &evaluate_pawns(board))
+}
+
+fn evaluate_classical(board: &Board, weights: &EvalWeights, pawns: &PawnEntry) -> Score {
+    let (mut mg, mut eg) = get_material_and_squares(board);
+    let (passed_mg, passed_eg) = get_passed_bonus(board, pawns);
+    mg += pawns.mg + passed_mg;
+    eg += pawns.eg + passed_eg;
+
+    let activity = get_activity(board, weights);
+    mg += activity.mobility.0 * weights.mobility / 100;
+    eg += activity.mobility.1 * weights.mobility / 100;
+    if board.variant.has_royal_king() {
+        let mut safety = 0;
+        if weights.king_attack != 0 {
+            let penalty = get_king_attack_penalty(&activity, First)
+                - get_king_attack_penalty(&activity, Second);
+            safety += penalty * weights.king_attack / 100;
+        }
+        if weights.open_files != 0 {
+            let penalty =
+                get_open_file_penalty(board, First) - get_open_file_penalty(board, Second);
+            safety += penalty * weights.open_files / 100;
+        }
+        if weights.pawn_shield != 0 {
+            let penalty = get_shield_penalty(board, First) - get_shield_penalty(board, Second);
+            safety += penalty * weights.pawn_shield / 100;
+        }
+        mg += safety;
+    }
+    taper(board, mg, eg)
+}
+
+/// The midgame and endgame material and piece-square bonuses from
+/// `First`'s point of view, in centipawns.
+fn get_material_and_squares(board: &Board) -> (i32, i32) {
+    let mut mg = 0;
+    let mut eg = 0;
+    for row in 0..board.size {
+        for col in 0..board.size {
+            let location = Location { row, col };
+            let piece = match board.get_piece_from_location(location) {
+                Some(piece) => piece,
+                None => continue,
+            };
+            let sign = if piece.color == First { 1 } else { -1 };
+            let worth = get_centipawn_worth(piece.kind, board.variant);
+            let (mg_bonus, eg_bonus) = get_square_bonus(piece, location, board.size);
+            mg += sign * (worth + mg_bonus);
+            eg += sign * (worth + eg_bonus);
+        }
+    }
+    for color in [First, Second] {
+        let sign = if color == First { 1 } else { -1 };
+        for kind in HAND_KINDS {
+            let worth = get_centipawn_worth(kind, board.variant);
+            let total = sign * board.get_hand_count(color, kind) as i32 * worth;
+            mg += total;
+            eg += total;
+        }
+    }
+    (mg, eg)
+}
+
+/// Blends midgame and endgame centipawns by the game phase.
+fn taper(board: &Board, mg: i32, eg: i32) -> Score {
+    let phase = board.get_phase();
+    (mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate_fen(fen: &str) -> Score {
+        evaluate(&Board::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn start_position_is_balanced() {
+        assert_eq!(evaluate_fen(START_FEN), 0);
+        let board = Board::from_fen(START_FEN).unwrap();
+        assert_eq!(board.get_phase(), MAX_PHASE);
+    }
+    #[test]
+    fn mirrored_positions_evaluate_to_opposites() {
+        let white = evaluate_fen("4k3/8/8/8/3N4/8/PP6/4K3 w - - 0 1");
+        let black = evaluate_fen("4k3/pp6/8/3n4/8/8/8/4K3 b - - 0 1");
+        assert!(white > 0);
+        assert_eq!(white, -black);
+    }
+    #[test]
+    fn centralised_knights_and_castled_kings_score_higher() {
+        let center = evaluate_fen("rnbqkbnr/pppppppp/8/8/3N4/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1");
+        let rim = evaluate_fen("rnbqkbnr/pppppppp/8/8/N7/8/PPPPPPPP/R1BQKBNR w KQkq - 0 1");
+        assert!(center > rim);
+        let castled = evaluate_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w kq - 0 1");
+        let exposed = evaluate_fen("rnbqkbnr/pppppppp/8/8/8/4K3/PPPPPPPP/RNBQ1R2 w kq - 0 1");
+        assert!(castled > exposed);
+    }
+    #[test]
+    fn mobility_rewards_open_lines() {
+        let weights = EvalWeights {
+            king_attack: 0,
+            open_files: 0,
+            pawn_shield: 0,
+            ..EvalWeights::default()
+        };
+        let activity = |fen: &str| get_activity(&Board::from_fen(fen).unwrap(), &weights);
+        let open = activity("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        let blocked = activity("4k3/8/8/8/8/8/P7/RN2K3 w - - 0 1");
+        assert_eq!(open.mobility, (2 * 10, 4 * 10));
+        assert!(blocked.mobility.0 < open.mobility.0);
+    }
+    #[test]
+    fn king_safety_terms_can_be_switched_off() {
+        // The queen and knight bear down on a king without a shield.
+        let board = Board::from_fen("6k1/8/8/8/8/5n2/6q1/6K1 w - - 0 1").unwrap();
+        let activity = get_activity(&board, &EvalWeights::default());
+        assert_eq!(activity.king_attackers, [2, 0]);
+        assert!(get_king_attack_penalty(&activity, First) < 0);
+        assert_eq!(get_king_attack_penalty(&activity, Second), 0);
+
+        let score = |weights| ClassicalEvaluator::new(weights).evaluate(&board);
+        let all = score(EvalWeights::default());
+        let off = EvalWeights {
+            mobility: 0,
+            king_attack: 0,
+            open_files: 0,
+            pawn_shield: 0,
+        };
+        let none = score(off);
+        assert!(all < none);
+        let only_attack = EvalWeights {
+            king_attack: 100,
+            ..off
+        };
+        let attack = score(only_attack);
+        assert!(all < attack && attack < none);
+    }
+    #[test]
+    fn evaluators_build_on_each_other() {
+        // A knight on the rim and an open king against a row of pawns.
+        let board = Board::from_fen("4k3/pppppppp/8/8/8/8/8/N3K3 w - - 0 1").unwrap();
+        let material = MaterialEvaluator.evaluate(&board);
+        let pst = PstEvaluator.evaluate(&board);
+        let classical = ClassicalEvaluator::default().evaluate(&board);
+        assert_eq!(material, get_piece_kind_score(Knight) - 800);
+        assert_ne!(pst, material);
+        assert_ne!(classical, pst);
+        assert_eq!(classical, evaluate(&board));
+
+        let mut board = board;
+        board.set_variant(Variant::Antichess);
+        assert_eq!(MaterialEvaluator.evaluate(&board), -material);
+        assert_eq!(ClassicalEvaluator::default().evaluate(&board), -material);
+    }
+    #[test]
+    fn kings_centralise_in_the_endgame() {
+        let board = Board::from_fen("8/8/8/3k4/8/8/8/K7 w - - 0 1").unwrap();
+        assert_eq!(board.get_phase(), 0);
+        assert!(evaluate(&board) < 0);
+    }
+}
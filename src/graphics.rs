@@ -1,15 +1,31 @@
-use crate::utils::{Board, Location, Piece, PieceColor::*, PieceKind::*};
+use contrapunct::crazyhouse::HAND_KINDS;
+use contrapunct::utils::{Action, Board, Location, Piece, PieceColor::*, PieceKind, PieceKind::*};
+use contrapunct::variant::Variant;
 use macroquad::prelude::*;
 
+/// The side of a square. In crazyhouse the board gives up one column to the
+/// pockets on its right.
+pub fn get_square_size(board: &Board) -> f32 {
+    let columns = if board.variant == Variant::Crazyhouse {
+        board.size + 1
+    } else {
+        board.size
+    };
+    f32::max(screen_width(), screen_height()) / columns as f32
+}
 pub async fn draw_piece(board: &Board, location: Location, piece: Piece) {
-    let square_size = f32::max(screen_width(), screen_height()) / board.size as f32;
+    let square_size = get_square_size(board);
     let position = Vec2::new(
         location.col as f32 * square_size,
         location.row as f32 * square_size,
     );
+    draw_piece_at(position, square_size, piece);
+}
+/// Draws `piece` in the square of side `square_size` whose top left corner
+/// is `position`.
+pub fn draw_piece_at(position: Vec2, square_size: f32, piece: Piece) {
     let constant = 0.8;
     let color = if piece.color == First { WHITE } else { BLACK };
-    let opposite_color = if piece.color == First { BLACK } else { WHITE };
 
     let far = square_size * constant;
     let close = square_size * (1.0 - constant);
@@ -19,10 +35,7 @@ pub async fn draw_piece(board: &Board, location: Location, piece: Piece) {
     let bottom_right = Vec2::new(far, far);
     let top_left = Vec2::new(close, close);
     let top_right = Vec2::new(far, close);
-    let bottom = Vec2::new(mid, far);
     let top = Vec2::new(mid, close);
-    let right = Vec2::new(far, mid);
-    let left = Vec2::new(close, mid);
     let center = Vec2::new(mid, mid);
 
     draw_triangle(
@@ -31,6 +44,40 @@ pub async fn draw_piece(board: &Board, location: Location, piece: Piece) {
         bottom_right + position,
         color,
     );
+    // Compound pieces are drawn with the marks of the pieces they combine.
+    let draw_rook = || {
+        draw_rectangle(
+            position.x + close,
+            position.y + far,
+            far - close,
+            -close,
+            color,
+        );
+        draw_rectangle(
+            position.x + close,
+            position.y + close,
+            far - close,
+            close,
+            color,
+        );
+    };
+    let draw_knight = || {
+        draw_triangle(
+            top_right + position,
+            bottom_right + position,
+            top_left + position,
+            color,
+        )
+    };
+    let draw_queen = || {
+        draw_triangle(
+            center + position,
+            top_left + position,
+            top_right + position,
+            color,
+        )
+    };
+    let draw_mitre = || draw_circle(position.x + mid, position.y + close, close / 2.0, color);
     match piece.kind {
         Pawn => {
             draw_circle(
@@ -40,28 +87,8 @@ pub async fn draw_piece(board: &Board, location: Location, piece: Piece) {
                 color,
             );
         }
-        Rook => {
-            draw_rectangle(
-                position.x + close,
-                position.y + far,
-                far - close,
-                -close,
-                color,
-            );
-            draw_rectangle(
-                position.x + close,
-                position.y + close,
-                far - close,
-                close,
-                color,
-            );
-        }
-        Knight => draw_triangle(
-            top_right + position,
-            bottom_right + position,
-            top_left + position,
-            color,
-        ),
+        Rook => draw_rook(),
+        Knight => draw_knight(),
         Bishop => {
             //
             // draw_ellipse(
@@ -73,12 +100,7 @@ pub async fn draw_piece(board: &Board, location: Location, piece: Piece) {
             //     opposite_color,
             // )
         }
-        Queen => draw_triangle(
-            center + position,
-            top_left + position,
-            top_right + position,
-            color,
-        ),
+        Queen => draw_queen(),
         King => {
             draw_circle(position.x + mid, position.y + close, close / 1.2, color);
             draw_circle(
@@ -94,6 +116,41 @@ pub async fn draw_piece(board: &Board, location: Location, piece: Piece) {
                 color,
             );
         }
+        Archbishop => {
+            draw_knight();
+            draw_mitre();
+        }
+        Chancellor => {
+            draw_rook();
+            draw_knight();
+        }
+        Amazon => {
+            draw_queen();
+            draw_knight();
+        }
+        Camel => {
+            draw_circle(position.x + mid - close, position.y + mid, close, color);
+            draw_circle(position.x + mid + close, position.y + mid, close, color);
+        }
+        Grasshopper => {
+            let thickness = close / 3.0;
+            draw_line(
+                position.x + close,
+                position.y + far,
+                position.x + mid,
+                position.y + close,
+                thickness,
+                color,
+            );
+            draw_line(
+                position.x + far,
+                position.y + far,
+                position.x + mid,
+                position.y + close,
+                thickness,
+                color,
+            );
+        }
     };
 }
 pub async fn draw_pieces(board: &Board) {
@@ -108,7 +165,7 @@ pub async fn draw_pieces(board: &Board) {
     }
 }
 pub async fn draw_board(board: &Board) {
-    let square_size = f32::max(screen_width(), screen_height()) / board.size as f32;
+    let square_size = get_square_size(board);
     for row in 0..board.size {
         for col in 0..board.size {
             let color = if (col + row) % 2 == 0 { GRAY } else { BROWN };
@@ -124,7 +181,7 @@ pub async fn draw_board(board: &Board) {
 }
 pub async fn highlight_square(board: &Board, location: Location) {
     let Location { row, col } = location;
-    let square_size = f32::max(screen_width(), screen_height()) / board.size as f32;
+    let square_size = get_square_size(board);
     let color = if (col + row) % 2 == 0 { GREEN } else { LIME };
     draw_rectangle(
         square_size * col as f32,
@@ -136,7 +193,7 @@ pub async fn highlight_square(board: &Board, location: Location) {
 }
 pub async fn circle_mark_square(board: &Board, location: Location) {
     let Location { row, col } = location;
-    let square_size = f32::max(screen_width(), screen_height()) / board.size as f32;
+    let square_size = get_square_size(board);
     let color = if (col + row) % 2 == 0 { GREEN } else { LIME };
     draw_circle(
         square_size * (col as f32 + 0.5),
@@ -151,7 +208,7 @@ pub async fn draw_check(board: &Board) {
         let king_location = board.get_location_from_piece(king).unwrap();
 
         let Location { row, col } = king_location;
-        let square_size = f32::max(screen_width(), screen_height()) / board.size as f32;
+        let square_size = get_square_size(board);
         draw_rectangle(
             square_size * col as f32,
             square_size * row as f32,
@@ -162,12 +219,131 @@ pub async fn draw_check(board: &Board) {
     }
 }
 
+/// The squares of the promotion picker for `action`, one per piece kind,
+/// running from the promotion square back toward the promoting side.
+pub fn get_promotion_picker(board: &Board, action: Action) -> Vec<(Location, PieceKind)> {
+    let direction: isize = if board.turn == First { 1 } else { -1 };
+    board
+        .rules
+        .promotion_kinds
+        .iter()
+        .enumerate()
+        .map(|(index, &kind)| {
+            let row = action.end.row as isize + direction * index as isize;
+            let location = Location {
+                row: row as usize,
+                col: action.end.col,
+            };
+            (location, kind)
+        })
+        .collect()
+}
+pub async fn draw_promotion_picker(board: &Board, action: Action) {
+    let square_size = get_square_size(board);
+    draw_rectangle(
+        0.,
+        0.,
+        square_size * board.size as f32,
+        square_size * board.size as f32,
+        Color::new(0., 0., 0., 0.5),
+    );
+    let color = board.turn;
+    for (location, kind) in get_promotion_picker(board, action) {
+        let Location { row, col } = location;
+        draw_rectangle(
+            square_size * col as f32,
+            square_size * row as f32,
+            square_size,
+            square_size,
+            LIGHTGRAY,
+        );
+        draw_piece(board, location, Piece::new(kind, color)).await;
+    }
+}
+
 pub fn get_mouse_input(board: &Board) -> Option<Location> {
-    let square_size = f32::max(screen_width(), screen_height()) / board.size as f32;
     if is_mouse_button_pressed(MouseButton::Left) {
-        let col = (mouse_position().0 / square_size) as usize;
-        let row = (mouse_position().1 / square_size) as usize;
-        return Some(Location { row, col });
+        return get_mouse_square(board);
     }
     None
 }
+/// The square under the mouse, if it is over the board.
+pub fn get_mouse_square(board: &Board) -> Option<Location> {
+    let square_size = get_square_size(board);
+    let (x, y) = mouse_position();
+    let col = (x / square_size) as usize;
+    let row = (y / square_size) as usize;
+    if x < 0. || y < 0. || col >= board.size || row >= board.size {
+        return None;
+    }
+    Some(Location { row, col })
+}
+
+/// The pocket slots of the crazyhouse panel with the piece and count each
+/// shows. `Second`'s hand fills the panel from the top, `First`'s from the
+/// bottom, as the sides sit on the board.
+pub fn get_pocket_slots(board: &Board) -> Vec<(Rect, Piece, u8)> {
+    if board.variant != Variant::Crazyhouse {
+        return Vec::new();
+    }
+    let square_size = get_square_size(board);
+    let x = square_size * board.size as f32;
+    let half = square_size * board.size as f32 / 2.;
+    let mut slots = Vec::new();
+    for color in [Second, First] {
+        let held: Vec<(PieceKind, u8)> = HAND_KINDS
+            .iter()
+            .map(|&kind| (kind, board.get_hand_count(color, kind)))
+            .filter(|&(_, count)| count > 0)
+            .collect();
+        let slot_size = f32::min(square_size, half / held.len().max(1) as f32);
+        for (index, (kind, count)) in held.into_iter().enumerate() {
+            let y = if color == Second {
+                slot_size * index as f32
+            } else {
+                half * 2. - slot_size * (index + 1) as f32
+            };
+            let rect = Rect::new(x, y, slot_size, slot_size);
+            slots.push((rect, Piece::new(kind, color), count));
+        }
+    }
+    slots
+}
+pub async fn draw_pockets(board: &Board) {
+    if board.variant != Variant::Crazyhouse {
+        return;
+    }
+    let square_size = get_square_size(board);
+    let height = square_size * board.size as f32;
+    draw_rectangle(height, 0., square_size, height, DARKGRAY);
+    for (rect, piece, count) in get_pocket_slots(board) {
+        draw_piece_at(rect.point(), rect.w, piece);
+        if count > 1 {
+            let text = count.to_string();
+            draw_text(
+                &text,
+                rect.x + rect.w * 0.75,
+                rect.y + rect.h * 0.3,
+                rect.h * 0.3,
+                YELLOW,
+            );
+        }
+    }
+}
+/// The kind whose pocket slot of the side to move was just clicked.
+pub fn get_pocket_input(board: &Board) -> Option<PieceKind> {
+    if !is_mouse_button_pressed(MouseButton::Left) {
+        return None;
+    }
+    let mouse = Vec2::from(mouse_position());
+    get_pocket_slots(board)
+        .into_iter()
+        .find(|(rect, piece, _)| piece.color == board.turn && rect.contains(mouse))
+        .map(|(_, piece, _)| piece.kind)
+}
+/// Draws a piece being dragged out of a pocket under the mouse.
+pub fn draw_dragged_piece(board: &Board, kind: PieceKind) {
+    let square_size = get_square_size(board);
+    let position = Vec2::from(mouse_position()) - Vec2::splat(square_size / 2.);
+    draw_piece_at(position, square_size, Piece::new(kind, board.turn));
+}
@@ -0,0 +1,18 @@
+pub mod bitboard;
+pub mod chess960;
+pub mod crazyhouse;
+pub mod engine;
+pub mod eval;
+pub mod movegen;
+pub mod notation;
+pub mod pawns;
+pub mod perft;
+pub mod pgn;
+pub mod score;
+pub mod setups;
+pub mod tt;
+pub mod uci;
+#[allow(unused_variables)]
+pub mod utils;
+pub mod variant;
+pub mod zobrist;
@@ -1,12 +1,17 @@
+use ::rand::Rng;
+use contrapunct::chess960::*;
+use contrapunct::engine::*;
+use contrapunct::pgn::*;
+use contrapunct::setups::*;
+use contrapunct::utils::*;
+use contrapunct::variant::*;
 use macroquad::prelude::*;
-mod engine;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Write};
+use std::time::Duration;
 mod graphics;
-#[allow(unused_variables, dead_code)]
-mod utils;
 
-use engine::*;
 use graphics::*;
-use utils::*;
 
 fn window_conf() -> Conf {
     Conf {
@@ -16,9 +21,50 @@ fn window_conf() -> Conf {
         ..Default::default()
     }
 }
+/// Undoes moves until it is a human's turn again, so that a takeback in a
+/// game against the engine does not immediately get replayed.
+fn take_back(board: &mut Board, players: &[Player; 2], current_player: &mut usize) {
+    let is_human = |player: &Player| matches!(player.kind, PlayerKind::Human);
+    let has_human = players.iter().any(is_human);
+    while board.unmake_move().is_some() {
+        *current_player = (*current_player + 1) % 2;
+        if !has_human || is_human(&players[*current_player]) {
+            break;
+        }
+    }
+    board.selected = None;
+}
+
+const GAMES_PATH: &str = "games.pgn";
+
+fn save_game(board: &Board) {
+    let game = Game::from_board(board);
+    let result = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(GAMES_PATH)
+        .and_then(|mut file| writeln!(file, "{}", game));
+    match result {
+        Ok(_) => println!("Game saved to {}", GAMES_PATH),
+        Err(error) => println!("Error: {:?}", error),
+    }
+}
+/// Replaces the board with the final position of the last game saved.
+fn load_game(board: &mut Board) -> Result<(), PgnError> {
+    let file = File::open(GAMES_PATH).map_err(|error| PgnError::Io(error.to_string()))?;
+    let mut last_game = None;
+    for game in PgnReader::new(BufReader::new(file)) {
+        last_game = Some(game?);
+    }
+    if let Some(game) = last_game {
+        *board = game.get_board()?;
+    }
+    Ok(())
+}
+
 #[macroquad::main(window_conf)]
 async fn main() {
-    let players = [
+    let mut players = [
         Player::new(PlayerKind::Random, 0),
         Player::new(PlayerKind::Pruning, 4),
     ];
@@ -26,10 +72,15 @@ async fn main() {
 
     let mut board = Board::new(8);
 
-    let start_fen = String::from("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR");
-    // let start_fen = String::from("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R");
-    // let start_fen = String::from("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1");
-    board.set_fen(&start_fen);
+    let start_fen = START_FEN;
+    // let start_fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    // let start_fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    board.set_fen(start_fen).expect("Invalid start FEN");
+
+    // A human promotion waiting for the piece to be picked.
+    let mut promotion: Option<Action> = None;
+    // A piece being dragged out of a crazyhouse pocket.
+    let mut dragging: Option<PieceKind> = None;
 
     let max_timer = 100;
     let mut timer = max_timer;
@@ -39,6 +90,63 @@ async fn main() {
         // println!("{:.2}", tree.value);
 
         timer -= 1;
+        if is_key_pressed(KeyCode::Backspace) {
+            take_back(&mut board, &players, &mut current_player);
+        }
+        if is_key_pressed(KeyCode::S) {
+            save_game(&board);
+        }
+        if is_key_pressed(KeyCode::L) {
+            match load_game(&mut board) {
+                Ok(_) => current_player = color_index(board.turn),
+                Err(error) => println!("Error: {:?}", error),
+            }
+        }
+        let setup_keys = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+        for (key, setup) in setup_keys.into_iter().zip(SETUPS) {
+            if is_key_pressed(key) {
+                board = Board::from_setup(setup);
+                current_player = 0;
+                promotion = None;
+                dragging = None;
+                println!("New game: {:?}", setup);
+            }
+        }
+        if is_key_pressed(KeyCode::N) {
+            let index = ::rand::thread_rng().gen_range(0..CHESS960_POSITIONS);
+            board = Board::new_chess960(index).unwrap();
+            current_player = 0;
+            promotion = None;
+            dragging = None;
+            println!("Chess960 position {}", index);
+        }
+        if is_key_pressed(KeyCode::I) {
+            // Switches the second player between a fixed depth and a fixed
+            // time per move.
+            players[1] = match players[1].kind {
+                PlayerKind::Iterative => {
+                    println!("Second player: alpha-beta to depth 4");
+                    Player::new(PlayerKind::Pruning, 4)
+                }
+                _ => {
+                    println!("Second player: iterative deepening, 1s per move");
+                    Player::new(PlayerKind::Iterative, 0).with_move_time(Duration::from_secs(1))
+                }
+            };
+        }
+        if is_key_pressed(KeyCode::V) {
+            // Cycles through the variants, each from the standard setup.
+            let index = VARIANTS
+                .iter()
+                .position(|&variant| variant == board.variant);
+            let variant = VARIANTS[index.map_or(0, |index| (index + 1) % VARIANTS.len())];
+            board = Board::from_fen(START_FEN).unwrap();
+            board.set_variant(variant);
+            current_player = 0;
+            promotion = None;
+            dragging = None;
+            println!("New game: {}", variant.get_name());
+        }
         draw_board(&board).await;
         draw_check(&board).await;
         if let Some(last_action) = board.last_action {
@@ -46,39 +154,104 @@ async fn main() {
             highlight_square(&board, last_action.end).await;
         }
         draw_pieces(&board).await;
+        draw_pockets(&board).await;
+        if let Some(action) = promotion {
+            draw_promotion_picker(&board, action).await;
+        }
 
-        if board.is_moveless() {
-            if board.is_check(board.turn) {
-                println!("Game over. {:?} wins", opposite_color(board.turn));
-            } else {
-                println!("Game over. It's a draw");
+        if let Some(outcome) = board.get_outcome() {
+            match outcome {
+                Outcome::Draw(draw) => println!("Game over. It's a draw: {:?}", draw),
+                Outcome::Win(winner) => println!("Game over. {:?} wins", winner),
             }
             next_frame().await;
         } else {
             if let Some(location) = board.selected {
                 let actions = board.get_valid_actions(location);
                 for action in actions {
-                    circle_mark_square(&board, action.end).await;
+                    // In Chess960 the king castles by moving onto its rook.
+                    let end = match action.kind {
+                        ActionKind::Castling(kind) if board.chess960 => board
+                            .get_castling_rook(board.turn, kind)
+                            .unwrap_or(action.end),
+                        _ => action.end,
+                    };
+                    circle_mark_square(&board, end).await;
                 }
             }
+            if let Some(kind) = dragging {
+                for action in board.get_all_valid_actions() {
+                    if action.kind == ActionKind::Drop(kind) {
+                        circle_mark_square(&board, action.end).await;
+                    }
+                }
+                draw_dragged_piece(&board, kind);
+            }
             next_frame().await;
 
             match &players[current_player].kind {
                 PlayerKind::Human => {
-                    if let Some(location) = get_mouse_input(&board) {
+                    if let Some(kind) = dragging {
+                        // The piece lands wherever the button is let go.
+                        if is_mouse_button_released(MouseButton::Left) {
+                            dragging = None;
+                            if let Some(location) = get_mouse_square(&board) {
+                                let action = Action {
+                                    start: location,
+                                    end: location,
+                                    kind: ActionKind::Drop(kind),
+                                };
+                                match board.commit_move(action) {
+                                    Ok(_) => current_player = (current_player + 1) % 2,
+                                    Err(error) => println!("Error: {:?}", error),
+                                }
+                            }
+                        }
+                    } else if let Some(kind) = get_pocket_input(&board) {
+                        dragging = Some(kind);
+                        board.selected = None;
+                    } else if let Some(action) = promotion {
+                        if let Some(location) = get_mouse_input(&board) {
+                            let picked = get_promotion_picker(&board, action)
+                                .into_iter()
+                                .find(|(square, _)| *square == location);
+                            // Clicking anywhere else cancels the promotion.
+                            if let Some((_, kind)) = picked {
+                                let action = Action {
+                                    kind: ActionKind::Promotion(kind),
+                                    ..action
+                                };
+                                match board.commit_move(action) {
+                                    Ok(_) => current_player = (current_player + 1) % 2,
+                                    Err(error) => println!("Error: {:?}", error),
+                                }
+                            }
+                            promotion = None;
+                            board.selected = None;
+                        }
+                    } else if let Some(location) = get_mouse_input(&board) {
                         let piece = board.get_piece_from_location(location);
-                        if board.selected == None {
+                        if board.selected.is_none() {
                             if let Some(piece) = piece {
                                 if piece.color == board.turn {
                                     board.selected = Some(location);
                                 }
                             }
                         } else {
-                            if piece != None && piece.unwrap().color == board.turn {
+                            let action =
+                                board.get_action_from_locations(board.selected.unwrap(), location);
+                            if piece.is_some()
+                                && piece.unwrap().color == board.turn
+                                && !matches!(action.kind, ActionKind::Castling(_))
+                            {
                                 board.selected = Some(location);
                             } else {
-                                let action = board
-                                    .get_action_from_locations(board.selected.unwrap(), location);
+                                if matches!(action.kind, ActionKind::Promotion(_))
+                                    && board.test_move(action).is_ok()
+                                {
+                                    promotion = Some(action);
+                                    continue;
+                                }
                                 let result = board.commit_move(action);
                                 match result {
                                     Ok(_) => {
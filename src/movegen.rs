@@ -0,0 +1,680 @@
+use crate::bitboard::*;
+use crate::utils::ActionKind::*;
+use crate::utils::CastlingKind::*;
+use crate::utils::PieceColor::*;
+use crate::utils::PieceKind::*;
+use crate::utils::*;
+use crate::variant::*;
+
+type Offset = (isize, isize);
+
+const ORTHOGONAL: [Offset; 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+const DIAGONAL: [Offset; 4] = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+const ROYAL: [Offset; 8] = [
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+    (0, -1),
+    (0, 1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+];
+const KNIGHT: [Offset; 8] = [
+    (-2, -1),
+    (-2, 1),
+    (-1, -2),
+    (-1, 2),
+    (1, -2),
+    (1, 2),
+    (2, -1),
+    (2, 1),
+];
+const CAMEL: [Offset; 8] = [
+    (-3, -1),
+    (-3, 1),
+    (-1, -3),
+    (-1, 3),
+    (1, -3),
+    (1, 3),
+    (3, -1),
+    (3, 1),
+];
+
+/// The single steps a piece takes, as (row, col) offsets.
+pub fn get_leaps(kind: PieceKind) -> &'static [Offset] {
+    match kind {
+        Knight | Archbishop | Chancellor | Amazon => &KNIGHT,
+        King => &ROYAL,
+        Camel => &CAMEL,
+        _ => &[],
+    }
+}
+/// The directions a piece slides in until it is blocked.
+pub fn get_rides(kind: PieceKind) -> &'static [Offset] {
+    match kind {
+        Rook | Chancellor => &ORTHOGONAL,
+        Bishop | Archbishop => &DIAGONAL,
+        Queen | Amazon => &ROYAL,
+        _ => &[],
+    }
+}
+
+/// The row offset of a pawn step for `color`.
+fn get_pawn_direction(color: PieceColor) -> isize {
+    if color == First {
+        -1
+    } else {
+        1
+    }
+}
+
+/// What the legal moves of the side to move have to respect, found by
+/// looking outward from its king.
+struct Constraints {
+    king: Option<Location>,
+    checkers: usize,
+    /// The squares that capture or block the only checker.
+    check_mask: Vec<bool>,
+    /// Pinned pieces with the direction from the king along the line they
+    /// are pinned on, and how many steps away the pinner stands.
+    pins: Vec<(Location, Offset, isize)>,
+}
+
+impl Constraints {
+    /// No king to look after: every pseudo-legal action is allowed.
+    fn none() -> Self {
+        Constraints {
+            king: None,
+            checkers: 0,
+            check_mask: Vec::new(),
+            pins: Vec::new(),
+        }
+    }
+    fn allows(&self, size: usize, start: Location, end: Location) -> bool {
+        if self.checkers > 1 {
+            return false;
+        }
+        if self.checkers == 1 && !self.check_mask[end.row * size + end.col] {
+            return false;
+        }
+        match self.pins.iter().find(|(pinned, _, _)| *pinned == start) {
+            Some(&(_, (dy, dx), reach)) => {
+                // A pinned piece stays between its king and the pinner, or
+                // takes the pinner. A grasshopper could otherwise hop past
+                // either of them.
+                let king = self.king.unwrap();
+                let ey = end.row as isize - king.row as isize;
+                let ex = end.col as isize - king.col as isize;
+                let steps = ey.abs().max(ex.abs());
+                ey == steps * dy && ex == steps * dx && steps <= reach
+            }
+            None => true,
+        }
+    }
+}
+
+impl Board {
+    fn get_offset_location(&self, location: Location, (dy, dx): Offset) -> Option<Location> {
+        let row = location.row as isize + dy;
+        let col = location.col as isize + dx;
+        let size = self.size as isize;
+        if (0..size).contains(&row) && (0..size).contains(&col) {
+            Some(Location {
+                row: row as usize,
+                col: col as usize,
+            })
+        } else {
+            None
+        }
+    }
+    /// The pieces of `color` attacking `square`, treating the `ignored`
+    /// squares as empty so that a king cannot hide behind itself.
+    pub(crate) fn get_attackers(
+        &self,
+        square: Location,
+        color: PieceColor,
+        ignored: &[Location],
+    ) -> Vec<Location> {
+        let mut attackers = Vec::new();
+        let is_attacker = |location: Location, attacks: &dyn Fn(PieceKind) -> bool| {
+            self.get_piece_from_location(location)
+                .is_some_and(|piece| piece.color == color && attacks(piece.kind))
+        };
+
+        let direction = get_pawn_direction(color);
+        for dx in [-1, 1] {
+            if let Some(location) = self.get_offset_location(square, (-direction, dx)) {
+                if is_attacker(location, &|kind| kind == Pawn) {
+                    attackers.push(location);
+                }
+            }
+        }
+        for leaps in [&KNIGHT[..], &ROYAL[..], &CAMEL[..]] {
+            for &(dy, dx) in leaps {
+                if let Some(location) = self.get_offset_location(square, (dy, dx)) {
+                    if is_attacker(location, &|kind| get_leaps(kind).contains(&(-dy, -dx))) {
+                        attackers.push(location);
+                    }
+                }
+            }
+        }
+        for &(dy, dx) in &ROYAL {
+            let mut current = square;
+            while let Some(location) = self.get_offset_location(current, (dy, dx)) {
+                current = location;
+                if ignored.contains(&location) || self.get_piece_from_location(location).is_none() {
+                    continue;
+                }
+                if is_attacker(location, &|kind| get_rides(kind).contains(&(-dy, -dx))) {
+                    attackers.push(location);
+                }
+                break;
+            }
+        }
+        // A grasshopper lands right behind the first piece on its line.
+        for &(dy, dx) in &ROYAL {
+            let hurdle = match self.get_offset_location(square, (dy, dx)) {
+                Some(hurdle) if !ignored.contains(&hurdle) => hurdle,
+                _ => continue,
+            };
+            if self.get_piece_from_location(hurdle).is_none() {
+                continue;
+            }
+            let mut current = hurdle;
+            while let Some(location) = self.get_offset_location(current, (dy, dx)) {
+                current = location;
+                if ignored.contains(&location) || self.get_piece_from_location(location).is_none() {
+                    continue;
+                }
+                if is_attacker(location, &|kind| kind == Grasshopper) {
+                    attackers.push(location);
+                }
+                break;
+            }
+        }
+        attackers
+    }
+    /// The squares the piece on `start` attacks, whether or not they hold a
+    /// piece of its own color. Empty for an empty square.
+    pub fn get_piece_attacks(&self, start: Location) -> Vec<Location> {
+        let piece = match self.get_piece_from_location(start) {
+            Some(piece) => piece,
+            None => return Vec::new(),
+        };
+        if let Some(bitboards) = self.get_bitboards() {
+            let attacks = bitboards.get_piece_attacks(piece, get_square(start));
+            return get_squares(attacks & get_board_mask(self.size))
+                .map(get_location)
+                .collect();
+        }
+        let mut attacks = Vec::new();
+        if piece.kind == Pawn {
+            let direction = get_pawn_direction(piece.color);
+            for dx in [-1, 1] {
+                attacks.extend(self.get_offset_location(start, (direction, dx)));
+            }
+        }
+        for &offset in get_leaps(piece.kind) {
+            attacks.extend(self.get_offset_location(start, offset));
+        }
+        for &offset in get_rides(piece.kind) {
+            let mut current = start;
+            while let Some(location) = self.get_offset_location(current, offset) {
+                attacks.push(location);
+                if self.get_piece_from_location(location).is_some() {
+                    break;
+                }
+                current = location;
+            }
+        }
+        if piece.kind == Grasshopper {
+            for &offset in &ROYAL {
+                let mut current = start;
+                while let Some(location) = self.get_offset_location(current, offset) {
+                    current = location;
+                    if self.get_piece_from_location(location).is_some() {
+                        attacks.extend(self.get_offset_location(location, offset));
+                        break;
+                    }
+                }
+            }
+        }
+        attacks
+    }
+    fn get_constraints(&self) -> Constraints {
+        let size = self.size;
+        let mut constraints = Constraints {
+            king: self.get_location_from_piece(Piece::new(King, self.turn)),
+            checkers: 0,
+            check_mask: vec![false; size * size],
+            pins: Vec::new(),
+        };
+        let king = match constraints.king {
+            Some(king) => king,
+            None => return constraints,
+        };
+        let enemy = opposite_color(self.turn);
+
+        for checker in self.get_attackers(king, enemy, &[]) {
+            constraints.checkers += 1;
+            constraints.check_mask[checker.row * size + checker.col] = true;
+            // The squares between a sliding checker and the king block it.
+            let ry = checker.row as isize - king.row as isize;
+            let rx = checker.col as isize - king.col as isize;
+            let (dy, dx) = (ry.signum(), rx.signum());
+            let aligned = ry == 0 || rx == 0 || ry.abs() == rx.abs();
+            let kind = self.get_piece_from_location(checker).unwrap().kind;
+            if aligned && get_rides(kind).contains(&(-dy, -dx)) {
+                let mut current = king;
+                while let Some(location) = self.get_offset_location(current, (dy, dx)) {
+                    if location == checker {
+                        break;
+                    }
+                    constraints.check_mask[location.row * size + location.col] = true;
+                    current = location;
+                }
+            }
+        }
+
+        for &direction in &ROYAL {
+            let mut current = king;
+            let mut pinned = None;
+            let mut steps = 0;
+            while let Some(location) = self.get_offset_location(current, direction) {
+                current = location;
+                steps += 1;
+                let piece = match self.get_piece_from_location(location) {
+                    Some(piece) => piece,
+                    None => continue,
+                };
+                if piece.color == self.turn {
+                    if pinned.is_some() {
+                        break;
+                    }
+                    pinned = Some(location);
+                    continue;
+                }
+                let (dy, dx) = direction;
+                if let Some(pinned) = pinned {
+                    if get_rides(piece.kind).contains(&(-dy, -dx)) {
+                        constraints.pins.push((pinned, direction, steps));
+                    }
+                }
+                break;
+            }
+        }
+        constraints
+    }
+    /// Emits the legal actions of the piece on `start` into `actions`.
+    fn generate_piece_actions(
+        &mut self,
+        start: Location,
+        constraints: &Constraints,
+        actions: &mut Vec<Action>,
+    ) {
+        let piece = match self.get_piece_from_location(start) {
+            Some(piece) if piece.color == self.turn => piece,
+            _ => return,
+        };
+        let size = self.size;
+        let enemy = opposite_color(piece.color);
+        let push = |actions: &mut Vec<Action>, end: Location, kind: ActionKind| {
+            if constraints.allows(size, start, end) {
+                actions.push(Action { start, end, kind });
+            }
+        };
+
+        match piece.kind {
+            Pawn => {
+                let direction = get_pawn_direction(piece.color);
+                let home_row = if piece.color == First { size - 2 } else { 1 };
+                let last_row = if piece.color == First { 0 } else { size - 1 };
+                let promotion_kinds = self.rules.promotion_kinds;
+                let push_step = |actions: &mut Vec<Action>, end: Location, kind: ActionKind| {
+                    if end.row == last_row {
+                        for &promotion in promotion_kinds {
+                            push(actions, end, Promotion(promotion));
+                        }
+                    } else {
+                        push(actions, end, kind);
+                    }
+                };
+                if let Some(end) = self.get_offset_location(start, (direction, 0)) {
+                    if self.get_piece_from_location(end).is_none() {
+                        push_step(actions, end, Normal);
+                        if self.rules.double_step && start.row == home_row {
+                            if let Some(end) = self.get_offset_location(end, (direction, 0)) {
+                                if self.get_piece_from_location(end).is_none() {
+                                    push(actions, end, Normal);
+                                }
+                            }
+                        }
+                    }
+                }
+                for dx in [-1, 1] {
+                    let end = match self.get_offset_location(start, (direction, dx)) {
+                        Some(end) => end,
+                        None => continue,
+                    };
+                    match self.get_piece_from_location(end) {
+                        Some(target) if target.color == enemy => push_step(actions, end, Capture),
+                        None if self.en_passant == Some(end) => {
+                            // The captured pawn leaves a square that neither
+                            // mask describes, so play it out instead.
+                            let action = Action {
+                                start,
+                                end,
+                                kind: EnPassant,
+                            };
+                            if self.test_move(action).is_ok() {
+                                actions.push(action);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            King => {
+                for &offset in get_leaps(King) {
+                    let end = match self.get_offset_location(start, offset) {
+                        Some(end) => end,
+                        None => continue,
+                    };
+                    let kind = match self.get_piece_from_location(end) {
+                        Some(target) if target.color == piece.color => continue,
+                        Some(_) => Capture,
+                        None => Normal,
+                    };
+                    // An atomic king may step next to the enemy king whatever
+                    // attacks the square, and an antichess king anywhere.
+                    let unchecked = matches!(self.variant, Variant::Atomic | Variant::Antichess);
+                    if unchecked || self.get_attackers(end, enemy, &[start]).is_empty() {
+                        actions.push(Action { start, end, kind });
+                    }
+                }
+                if constraints.checkers == 0 {
+                    for ckind in [Short, Long] {
+                        match self.get_castling_action(ckind) {
+                            Some(action) if action.start == start && self.can_castle(ckind) => {
+                                actions.push(action)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+            }
+            Grasshopper => {
+                for &offset in &ROYAL {
+                    let mut current = start;
+                    while let Some(location) = self.get_offset_location(current, offset) {
+                        current = location;
+                        if self.get_piece_from_location(location).is_some() {
+                            break;
+                        }
+                    }
+                    if current == start || self.get_piece_from_location(current).is_none() {
+                        continue;
+                    }
+                    if let Some(end) = self.get_offset_location(current, offset) {
+                        match self.get_piece_from_location(end) {
+                            Some(target) if target.color == piece.color => {}
+                            Some(_) => push(actions, end, Capture),
+                            None => push(actions, end, Normal),
+                        }
+                    }
+                }
+            }
+            kind => {
+                for &offset in get_leaps(kind) {
+                    if let Some(end) = self.get_offset_location(start, offset) {
+                        match self.get_piece_from_location(end) {
+                            Some(target) if target.color == piece.color => {}
+                            Some(_) => push(actions, end, Capture),
+                            None => push(actions, end, Normal),
+                        }
+                    }
+                }
+                for &offset in get_rides(kind) {
+                    let mut current = start;
+                    while let Some(end) = self.get_offset_location(current, offset) {
+                        current = end;
+                        match self.get_piece_from_location(end) {
+                            Some(target) => {
+                                if target.color != piece.color {
+                                    push(actions, end, Capture);
+                                }
+                                break;
+                            }
+                            None => push(actions, end, Normal),
+                        }
+                    }
+                }
+            }
+        }
+    }
+    fn generate_constrained_actions(
+        &mut self,
+        start: Option<Location>,
+        constraints: &Constraints,
+        actions: &mut Vec<Action>,
+    ) {
+        match start {
+            Some(start) => self.generate_piece_actions(start, constraints, actions),
+            None => {
+                for row in 0..self.size {
+                    for col in 0..self.size {
+                        let start = Location { row, col };
+                        self.generate_piece_actions(start, constraints, actions);
+                    }
+                }
+                let size = self.size;
+                self.generate_drops(|end| constraints.allows(size, end, end), actions);
+            }
+        }
+    }
+    /// The actions of the side to move without regard for its king, which
+    /// in antichess are exactly the legal ones before captures are forced.
+    pub(crate) fn generate_pseudo_actions(&mut self) -> Vec<Action> {
+        let mut actions = Vec::new();
+        self.generate_constrained_actions(None, &Constraints::none(), &mut actions);
+        actions
+    }
+    /// Generates the legal actions of the piece on `start`, or of every
+    /// piece of the side to move.
+    pub fn generate_actions(&mut self, start: Option<Location>) -> Vec<Action> {
+        // Moving a piece onto a grasshopper's line can give it a hurdle, so
+        // pins and the check mask miss some of its checks. With one on the
+        // other side every action is played out instead.
+        let enemy = opposite_color(self.turn);
+        let hoppers = match self.get_bitboards() {
+            Some(bitboards) => bitboards.get_pieces(Grasshopper, enemy) != 0,
+            None => self.position.iter().flatten().any(|&piece| {
+                piece.is_some_and(|piece| piece.kind == Grasshopper && piece.color == enemy)
+            }),
+        };
+        // Explosions change the board too much for the masks. Antichess has
+        // no king to protect, so its actions are legal as generated.
+        let played_out = match self.variant {
+            Variant::Antichess => false,
+            Variant::Atomic => true,
+            _ => hoppers,
+        };
+        let constraints = if played_out || self.variant == Variant::Antichess {
+            Constraints::none()
+        } else {
+            self.get_constraints()
+        };
+        let mut actions = Vec::new();
+        self.generate_constrained_actions(start, &constraints, &mut actions);
+        if played_out {
+            actions.retain(|&action| self.test_move(action).is_ok());
+        }
+        if self.variant == Variant::Antichess {
+            // A single piece's captures are forced by those of the others too.
+            let forced = match start {
+                Some(_) => self.has_capture(),
+                None => actions.iter().any(|&action| self.is_capture(action)),
+            };
+            if forced {
+                actions.retain(|&action| self.is_capture(action));
+            }
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAIRY_POSITIONS: [&str; 4] = [
+        "r1a1k2r/1p3p2/2z2l2/3G4/4g3/2L2c2/1P3P2/R1C1K1ZR w KQkq - 0 1",
+        "4g2k/8/8/8/8/8/R7/4K3 w - - 0 1",
+        "r1bqkcabnr/pppppppppp/10/5g4/10/10/3G6/10/PPPPPPPPPP/RNBQKCAB1R b KQkq - 0 1",
+        // A pinned grasshopper must not hop over its king.
+        "k3r3/8/8/8/8/4G3/4K3/8 w - - 0 1",
+    ];
+
+    fn count(fen: &str) -> usize {
+        Board::from_fen(fen).unwrap().generate_actions(None).len()
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_their_line() {
+        // A bishop pinned on a file cannot move at all.
+        let mut board = Board::from_fen("4r1k1/8/8/8/8/8/4B3/4K3 w - - 0 1").unwrap();
+        let actions = board.generate_actions(Some(Location { row: 6, col: 4 }));
+        assert!(actions.is_empty());
+        // One pinned on a diagonal may move along it.
+        let mut board = Board::from_fen("4k3/8/8/8/5q2/8/3B4/2K5 w - - 0 1").unwrap();
+        let actions = board.generate_actions(Some(Location { row: 6, col: 3 }));
+        let ends: Vec<String> = actions
+            .iter()
+            .map(|&action| action_to_uci(action, board.size))
+            .collect();
+        assert_eq!(ends, ["d2e3", "d2f4"]);
+        // A pinned grasshopper may not hop over its king.
+        let mut board = Board::from_fen(FAIRY_POSITIONS[3]).unwrap();
+        let actions = board.generate_actions(Some(Location { row: 5, col: 4 }));
+        assert!(actions.is_empty());
+    }
+    #[test]
+    fn checks_are_captured_blocked_or_evaded() {
+        // Rook check on the e-file: Re2 blocks, Kd1/Kf1/Kd2/Kf2 step aside.
+        assert_eq!(count("4r1k1/8/8/8/8/8/R7/4K3 w - - 0 1"), 5);
+        // Double check leaves only king moves.
+        assert_eq!(count("4r1k1/8/8/8/8/3n4/R7/4K3 w - - 0 1"), 3);
+    }
+    #[test]
+    fn en_passant_along_a_pinned_rank_is_illegal() {
+        assert_eq!(count("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1"), 6);
+    }
+    #[test]
+    fn other_board_sizes() {
+        let mut board = Board::from_fen("rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1").unwrap();
+        for action in board.generate_actions(None) {
+            assert!(board.test_move(action).is_ok());
+        }
+        let mut board = Board::from_fen("10/10/10/4k5/10/10/4R5/10/10/4K5 b - - 0 1").unwrap();
+        assert_eq!(board.generate_actions(None).len(), 6);
+    }
+    #[test]
+    fn fairy_pieces() {
+        // Camel from a1 to b4 and d2, archbishop a knight and a bishop.
+        assert_eq!(count("7k/8/8/8/8/8/8/L3K3 w - - 0 1"), 7);
+        assert_eq!(count("7k/8/8/8/8/8/8/4K2A w - - 0 1"), 5 + 9);
+        // A grasshopper needs a hurdle and lands right behind it.
+        assert_eq!(count("7k/8/8/8/8/8/8/G1P1K3 w - - 0 1"), 5 + 1 + 1);
+        // Moving the rook to e2 would give the grasshopper a hurdle in front
+        // of the king.
+        let mut board = Board::from_fen("4g2k/8/8/8/8/8/R7/4K3 w - - 0 1").unwrap();
+        let actions = board.generate_actions(None);
+        assert_eq!(actions.len(), 13 + 5);
+        let rook_to_e2 = board.get_action_from_uci("a2e2").unwrap();
+        assert!(!actions.contains(&rook_to_e2));
+        assert!(board.test_move(rook_to_e2).is_err());
+    }
+    #[test]
+    fn piece_attacks_agree_with_attackers() {
+        let boards = crate::setups::SETUPS
+            .map(Board::from_setup)
+            .into_iter()
+            .chain(FAIRY_POSITIONS.map(|fen| Board::from_fen(fen).unwrap()));
+        for board in boards {
+            let squares: Vec<Location> = (0..board.size * board.size)
+                .map(|square| Location {
+                    row: square / board.size,
+                    col: square % board.size,
+                })
+                .collect();
+            for &square in &squares {
+                for color in [First, Second] {
+                    let mut attackers = board.get_attackers(square, color, &[]);
+                    let mut expected: Vec<Location> = squares
+                        .iter()
+                        .copied()
+                        .filter(|&start| {
+                            board
+                                .get_piece_from_location(start)
+                                .is_some_and(|piece| piece.color == color)
+                                && board.get_piece_attacks(start).contains(&square)
+                        })
+                        .collect();
+                    attackers.sort_by_key(|location| (location.row, location.col));
+                    expected.sort_by_key(|location| (location.row, location.col));
+                    assert_eq!(attackers, expected, "{}", board.get_fen());
+                }
+            }
+        }
+    }
+    /// Checks the generated actions against every start and end square
+    /// that `test_move` accepts.
+    fn check_against_every_square(board: &mut Board) {
+        let mut tried = Vec::new();
+        for start in 0..board.size * board.size {
+            for end in 0..board.size * board.size {
+                let start = Location {
+                    row: start / board.size,
+                    col: start % board.size,
+                };
+                let end = Location {
+                    row: end / board.size,
+                    col: end % board.size,
+                };
+                let action = board.get_action_from_locations(start, end);
+                if board.test_move(action).is_err() || tried.contains(&action) {
+                    continue;
+                }
+                match action.kind {
+                    Promotion(_) => {
+                        tried.extend(board.rules.promotion_kinds.iter().map(|&kind| Action {
+                            kind: Promotion(kind),
+                            ..action
+                        }))
+                    }
+                    _ => tried.push(action),
+                }
+            }
+        }
+        let generated = board.generate_actions(None);
+        assert_eq!(generated.len(), tried.len(), "{}", board.get_fen());
+        assert!(generated.iter().all(|action| tried.contains(action)));
+    }
+    #[test]
+    fn agrees_with_trying_every_square() {
+        let boards = crate::perft::PERFT_POSITIONS
+            .iter()
+            .map(|(_, fen, _)| Board::from_fen(fen).unwrap())
+            .chain(crate::setups::SETUPS.map(Board::from_setup))
+            .chain(FAIRY_POSITIONS.map(|fen| Board::from_fen(fen).unwrap()));
+        for mut board in boards {
+            check_against_every_square(&mut board);
+            for action in board.generate_actions(None) {
+                board.make_move(action);
+                check_against_every_square(&mut board);
+                board.unmake_move();
+            }
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::utils::ActionKind::*;
+use crate::utils::CastlingKind::*;
+use crate::utils::PieceKind::*;
+use crate::utils::*;
+
+#[derive(Debug, PartialEq)]
+pub enum NotationError {
+    InvalidSan,
+    NoMatchingMove,
+    AmbiguousMove,
+}
+
+/// Formats a legal `action` in Standard Algebraic Notation, including the
+/// disambiguation and the check or mate suffix.
+pub fn action_to_san(board: &mut Board, action: Action) -> String {
+    let Action { start, end, kind } = action;
+    let mut san = match kind {
+        Castling(Short) => String::from("O-O"),
+        Castling(Long) => String::from("O-O-O"),
+        Drop(kind) => format!(
+            "{}@{}",
+            get_piece_kind_letter(kind).to_ascii_uppercase(),
+            location_to_algebraic(end, board.size)
+        ),
+        _ => {
+            let piece = board.get_piece_from_location(start).unwrap();
+            let is_capture = kind == Capture
+                || kind == EnPassant
+                || board.get_piece_from_location(end).is_some();
+            let mut san = String::new();
+            if piece.kind == Pawn {
+                if is_capture {
+                    san.push(get_file_letter(start.col));
+                }
+            } else {
+                san.push(get_piece_kind_letter(piece.kind).to_ascii_uppercase());
+                san.push_str(&get_disambiguation(board, action, piece.kind));
+            }
+            if is_capture {
+                san.push('x');
+            }
+            san.push_str(&location_to_algebraic(end, board.size));
+            if let Promotion(promotion) = kind {
+                san.push('=');
+                san.push(get_piece_kind_letter(promotion).to_ascii_uppercase());
+            }
+            san
+        }
+    };
+
+    board.make_move(action);
+    if board.is_check(board.turn) {
+        if board.is_moveless() {
+            san.push('#');
+        } else {
+            san.push('+');
+        }
+    }
+    board.unmake_move();
+    san
+}
+
+fn get_file_letter(col: usize) -> char {
+    (col as u8 + b'a') as char
+}
+
+/// Returns the file, rank or square needed to tell `action` apart from the
+/// other legal moves of the same piece kind to the same square.
+fn get_disambiguation(board: &mut Board, action: Action, kind: PieceKind) -> String {
+    let Action { start, end, .. } = action;
+    let rivals: Vec<Location> = board
+        .get_all_valid_actions()
+        .iter()
+        .filter(|other| other.end == end && other.start != start)
+        .filter(|other| board.get_piece_from_location(other.start).map(|p| p.kind) == Some(kind))
+        .map(|other| other.start)
+        .collect();
+
+    let square = location_to_algebraic(start, board.size);
+    if rivals.is_empty() {
+        String::new()
+    } else if rivals.iter().all(|rival| rival.col != start.col) {
+        get_file_letter(start.col).to_string()
+    } else if rivals.iter().all(|rival| rival.row != start.row) {
+        square[1..].to_string()
+    } else {
+        square
+    }
+}
+
+/// Finds the legal action described by `san`. Accepts the common spellings
+/// other tools produce: `0-0` castling, promotions without `=`, annotation
+/// glyphs and an `e.p.` marker.
+pub fn san_to_action(board: &mut Board, san: &str) -> Result<Action, NotationError> {
+    let mut san = san.trim();
+    if let Some(stripped) = san.strip_suffix("e.p.") {
+        san = stripped.trim_end();
+    }
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+    let actions = board.get_all_valid_actions();
+
+    let castling = match san {
+        "O-O" | "0-0" => Some(Short),
+        "O-O-O" | "0-0-0" => Some(Long),
+        _ => None,
+    };
+    if let Some(castling) = castling {
+        return actions
+            .into_iter()
+            .find(|action| action.kind == Castling(castling))
+            .ok_or(NotationError::NoMatchingMove);
+    }
+
+    // Drops name the piece, pawns included, and the square: `N@f3`, `@e4`.
+    if let Some((letter, square)) = san.split_once('@') {
+        let kind = match letter {
+            "" => Pawn,
+            _ => letter
+                .chars()
+                .next()
+                .and_then(get_piece_kind_from_letter)
+                .filter(|_| letter.len() == 1)
+                .ok_or(NotationError::InvalidSan)?,
+        };
+        let end =
+            algebraic_to_location(square, board.size).map_err(|_| NotationError::InvalidSan)?;
+        return actions
+            .into_iter()
+            .find(|action| action.kind == Drop(kind) && action.end == end)
+            .ok_or(NotationError::NoMatchingMove);
+    }
+
+    let mut chars: Vec<char> = san.chars().collect();
+
+    // Piece letters are upper case, files lower case.
+    let get_piece_kind = |letter: char| match get_piece_kind_from_letter(letter) {
+        Some(Pawn) => None,
+        kind if letter.is_ascii_uppercase() => kind,
+        _ => None,
+    };
+
+    let mut promotion = None;
+    if let Some(&last) = chars.last() {
+        let promotes = |kind| kind != King || board.rules.promotion_kinds.contains(&King);
+        if get_piece_kind(last).is_some_and(promotes) {
+            promotion = get_piece_kind(last);
+            chars.pop();
+            if chars.last() == Some(&'=') {
+                chars.pop();
+            }
+        }
+    }
+
+    let kind = match chars.first().and_then(|&letter| get_piece_kind(letter)) {
+        Some(kind) => {
+            chars.remove(0);
+            kind
+        }
+        None => Pawn,
+    };
+
+    // The destination is the trailing file letter and rank digits.
+    let digits = chars
+        .iter()
+        .rev()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    if digits == 0 || chars.len() < digits + 1 {
+        return Err(NotationError::InvalidSan);
+    }
+    let square_start = chars.len() - digits - 1;
+    let square: String = chars[square_start..].iter().collect();
+    let end = algebraic_to_location(&square, board.size).map_err(|_| NotationError::InvalidSan)?;
+
+    let mut from_file = None;
+    let mut from_rank = None;
+    let prefix: Vec<char> = chars[..square_start]
+        .iter()
+        .copied()
+        .filter(|&c| c != 'x' && c != ':')
+        .collect();
+    for (i, c) in prefix.iter().enumerate() {
+        if c.is_ascii_lowercase() && i == 0 {
+            from_file = Some((*c as u8 - b'a') as usize);
+        } else if c.is_ascii_digit() {
+            let rank: String = prefix[i..].iter().collect();
+            let rank: usize = rank.parse().map_err(|_| NotationError::InvalidSan)?;
+            if rank == 0 || rank > board.size {
+                return Err(NotationError::InvalidSan);
+            }
+            from_rank = Some(board.size - rank);
+            break;
+        } else {
+            return Err(NotationError::InvalidSan);
+        }
+    }
+
+    let mut candidates: Vec<Action> = Vec::new();
+    for action in actions {
+        let piece = board.get_piece_from_location(action.start).unwrap();
+        if piece.kind != kind || action.end != end || matches!(action.kind, Castling(_)) {
+            continue;
+        }
+        if from_file.is_some_and(|col| col != action.start.col)
+            || from_rank.is_some_and(|row| row != action.start.row)
+        {
+            continue;
+        }
+        match (action.kind, promotion) {
+            (Promotion(generated), Some(requested)) if generated == requested => {}
+            (Promotion(_), _) | (_, Some(_)) => continue,
+            _ => {}
+        }
+        candidates.push(action);
+    }
+
+    match candidates.len() {
+        0 => Err(NotationError::NoMatchingMove),
+        1 => Ok(candidates[0]),
+        _ => Err(NotationError::AmbiguousMove),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(fen: &str, san: &str) {
+        let mut board = Board::from_fen(fen).unwrap();
+        let action = san_to_action(&mut board, san).unwrap();
+        assert_eq!(action_to_san(&mut board, action), san);
+    }
+
+    #[test]
+    fn formats_and_parses_common_moves() {
+        round_trip(START_FEN, "e4");
+        round_trip(START_FEN, "Nf3");
+        round_trip(
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+            "Nxe5",
+        );
+    }
+    #[test]
+    fn disambiguates_by_file_rank_and_square() {
+        let fen = "4k3/8/8/8/8/8/6K1/R6R w - - 0 1";
+        round_trip(fen, "Rab1");
+        let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+        round_trip(fen, "R1a3");
+        let fen = "1k6/8/8/8/4Q2Q/8/8/K6Q w - - 0 1";
+        round_trip(fen, "Qh4e1");
+    }
+    #[test]
+    fn castling_promotion_and_en_passant() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        round_trip(fen, "O-O");
+        round_trip(fen, "O-O-O");
+        let mut board = Board::from_fen(fen).unwrap();
+        let action = san_to_action(&mut board, "0-0").unwrap();
+        assert_eq!(action.kind, Castling(Short));
+
+        let fen = "3r4/4P3/8/8/8/8/8/k3K3 w - - 0 1";
+        round_trip(fen, "e8=Q");
+        round_trip(fen, "exd8=N");
+        let mut board = Board::from_fen(fen).unwrap();
+        let action = san_to_action(&mut board, "e8Q").unwrap();
+        assert_eq!(action.kind, Promotion(Queen));
+
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        round_trip(fen, "exf6");
+        let mut board = Board::from_fen(fen).unwrap();
+        let action = san_to_action(&mut board, "exf6 e.p.").unwrap();
+        assert_eq!(action.kind, EnPassant);
+    }
+    #[test]
+    fn fairy_pieces() {
+        let fen = "7k/8/8/8/8/8/8/L3K2A w - - 0 1";
+        round_trip(fen, "Lb4");
+        round_trip(fen, "Af2");
+        round_trip(fen, "Ab7");
+        round_trip("3k4/3P4/8/8/8/8/8/G1P1K3 w - - 0 1", "Gd1+");
+        assert_eq!(Board::from_fen(fen).unwrap().get_fen(), fen);
+    }
+    #[test]
+    fn check_and_mate_suffixes() {
+        round_trip("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "Ra8#");
+        round_trip("6k1/5pp1/8/8/8/8/8/R5K1 w - - 0 1", "Ra8+");
+        let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+        round_trip(fen, "Qxf7#");
+    }
+    #[test]
+    fn rejects_bad_input() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        assert_eq!(
+            san_to_action(&mut board, "e5"),
+            Err(NotationError::NoMatchingMove)
+        );
+        assert_eq!(
+            san_to_action(&mut board, "Zz9"),
+            Err(NotationError::InvalidSan)
+        );
+        assert_eq!(
+            san_to_action(&mut board, ""),
+            Err(NotationError::InvalidSan)
+        );
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/6K1/R6R w - - 0 1").unwrap();
+        assert_eq!(
+            san_to_action(&mut board, "Rd1"),
+            Err(NotationError::AmbiguousMove)
+        );
+    }
+}
@@ -0,0 +1,360 @@
+use crate::eval::*;
+use crate::utils::PieceColor::*;
+use crate::utils::PieceKind::*;
+use crate::utils::*;
+use crate::zobrist::MAX_SIZE;
+use std::mem::size_of;
+use std::ops::Range;
+
+pub const DEFAULT_PAWN_HASH_SIZE: usize = 1;
+
+// Midgame and endgame scores in centipawns.
+const DOUBLED: (i32, i32) = (-10, -20);
+const ISOLATED: (i32, i32) = (-15, -10);
+const BACKWARD: (i32, i32) = (-10, -10);
+/// For each pair of pawns standing side by side.
+const PHALANX: (i32, i32) = (10, 5);
+/// Passed pawn bonuses by how far the pawn has come, scaled to eight rows.
+const PASSED_MG: [i32; 8] = [0, 5, 10, 15, 25, 40, 60, 0];
+const PASSED_EG: [i32; 8] = [0, 10, 20, 35, 60, 100, 150, 0];
+/// A passed pawn with an enemy piece right in front of it keeps this share
+/// of its bonus, in percent.
+const BLOCKADED_SHARE: i32 = 50;
+/// Midgame penalties for a file next to the king whose shield pawn has
+/// advanced two squares, or is missing.
+const SHIELD_ADVANCED: i32 = -10;
+const SHIELD_MISSING: i32 = -25;
+/// Midgame penalties for a file next to the king without a pawn of its
+/// own, or without any pawn at all.
+const SEMI_OPEN_FILE: i32 = -15;
+const OPEN_FILE: i32 = -25;
+
+/// The pawn structure of a position, scored from `First`'s point of view.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PawnEntry {
+    pub hash: u64,
+    pub mg: i32,
+    pub eg: i32,
+    /// The row of the front passed pawn on each column, by color. Its
+    /// bonus depends on what stands in front of it, so it is left to the
+    /// evaluation.
+    pub passed: [[Option<u8>; MAX_SIZE]; 2],
+}
+
+/// A fixed-size table of pawn structure scores indexed by pawn hash. Pawns
+/// move rarely, so most lookups during a search hit.
+pub struct PawnTable {
+    entries: Vec<Option<PawnEntry>>,
+}
+
+impl PawnTable {
+    /// Creates a table that takes up about `size` megabytes.
+    pub fn new(size: usize) -> Self {
+        let count = (size.max(1) * 1024 * 1024 / size_of::<Option<PawnEntry>>()).max(1);
+        PawnTable {
+            entries: vec![None; count],
+        }
+    }
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn probe(&self, hash: u64) -> Option<PawnEntry> {
+        let index = (hash % self.entries.len() as u64) as usize;
+        self.entries[index].filter(|entry| entry.hash == hash)
+    }
+    /// The pawn structure of `board`, evaluated and stored on a miss.
+    pub fn get(&mut self, board: &Board) -> PawnEntry {
+        let hash = board.pawn_hash();
+        if let Some(entry) = self.probe(hash) {
+            return entry;
+        }
+        let entry = evaluate_pawns(board);
+        let index = (hash % self.entries.len() as u64) as usize;
+        self.entries[index] = Some(entry);
+        entry
+    }
+}
+
+/// The row a pawn of `color` moves towards.
+fn get_forward(color: PieceColor) -> isize {
+    if color == First {
+        -1
+    } else {
+        1
+    }
+}
+
+fn has_pawn(board: &Board, color: PieceColor, row: isize, col: isize) -> bool {
+    let size = board.size as isize;
+    if row < 0 || row >= size || col < 0 || col >= size {
+        return false;
+    }
+    let location = Location {
+        row: row as usize,
+        col: col as usize,
+    };
+    board
+        .get_piece_from_location(location)
+        .is_some_and(|piece| piece.kind == Pawn && piece.color == color)
+}
+
+/// Whether a pawn of `color` stands on `col` anywhere in the rows `rows`.
+fn has_pawn_in_rows(board: &Board, color: PieceColor, col: isize, mut rows: Range<isize>) -> bool {
+    rows.any(|row| has_pawn(board, color, row, col))
+}
+
+/// The rows strictly in front of `row` from `color`'s side.
+fn rows_ahead(board: &Board, color: PieceColor, row: isize) -> Range<isize> {
+    if color == First {
+        0..row
+    } else {
+        row + 1..board.size as isize
+    }
+}
+
+/// The rows level with or behind `row` from `color`'s side.
+fn rows_behind(board: &Board, color: PieceColor, row: isize) -> Range<isize> {
+    if color == First {
+        row..board.size as isize
+    } else {
+        0..row + 1
+    }
+}
+
+/// Scores the doubled, isolated, backward and phalanx pawns of both sides
+/// and finds their passed pawns.
+pub fn evaluate_pawns(board: &Board) -> PawnEntry {
+    let mut entry = PawnEntry {
+        hash: board.pawn_hash(),
+        mg: 0,
+        eg: 0,
+        passed: [[None; MAX_SIZE]; 2],
+    };
+    for row in 0..board.size as isize {
+        for col in 0..board.size as isize {
+            let color = match board.get_piece_from_location(Location {
+                row: row as usize,
+                col: col as usize,
+            }) {
+                Some(piece) if piece.kind == Pawn => piece.color,
+                _ => continue,
+            };
+            let enemy = opposite_color(color);
+            let forward = get_forward(color);
+            let sign = if color == First { 1 } else { -1 };
+            let mut add = |(mg, eg): (i32, i32)| {
+                entry.mg += sign * mg;
+                entry.eg += sign * eg;
+            };
+
+            let doubled = has_pawn_in_rows(board, color, col, rows_ahead(board, color, row));
+            if doubled {
+                add(DOUBLED);
+            }
+            let neighbours = [col - 1, col + 1];
+            let isolated = !neighbours
+                .iter()
+                .any(|&file| has_pawn_in_rows(board, color, file, 0..board.size as isize));
+            if isolated {
+                add(ISOLATED);
+            }
+            if has_pawn(board, color, row, col + 1) {
+                add(PHALANX);
+            }
+            let passed = !doubled
+                && !(col - 1..=col + 1).any(|file| {
+                    has_pawn_in_rows(board, enemy, file, rows_ahead(board, color, row))
+                });
+            if passed {
+                entry.passed[color_index(color)][col as usize] = Some(row as u8);
+                continue;
+            }
+            // A pawn that its neighbours can no longer support and whose
+            // advance an enemy pawn stops.
+            let supported = neighbours
+                .iter()
+                .any(|&file| has_pawn_in_rows(board, color, file, rows_behind(board, color, row)));
+            let stop_attacked = neighbours
+                .iter()
+                .any(|&file| has_pawn(board, enemy, row + 2 * forward, file));
+            if !isolated && !supported && stop_attacked {
+                add(BACKWARD);
+            }
+        }
+    }
+    entry
+}
+
+/// The midgame and endgame bonus of the passed pawns in `entry`, which
+/// grows as they advance and shrinks when an enemy piece blockades them.
+pub fn get_passed_bonus(board: &Board, entry: &PawnEntry) -> (i32, i32) {
+    let mut mg = 0;
+    let mut eg = 0;
+    for color in [First, Second] {
+        let sign = if color == First { 1 } else { -1 };
+        for (col, row) in entry.passed[color_index(color)].iter().enumerate() {
+            let row = match row {
+                Some(row) => *row as usize,
+                None => continue,
+            };
+            // A pawn on the far row, which a FEN may hold, has nowhere to go.
+            let stop_row = row as isize + get_forward(color);
+            if stop_row < 0 || stop_row >= board.size as isize {
+                continue;
+            }
+            let advanced = if color == First {
+                board.size - 1 - row
+            } else {
+                row
+            };
+            let rank = scale_to_table(advanced, board.size);
+            let mut bonus = (PASSED_MG[rank], PASSED_EG[rank]);
+            let stop = Location {
+                row: stop_row as usize,
+                col,
+            };
+            if board
+                .get_piece_from_location(stop)
+                .is_some_and(|piece| piece.color != color)
+            {
+                bonus.0 = bonus.0 * BLOCKADED_SHARE / 100;
+                bonus.1 = bonus.1 * BLOCKADED_SHARE / 100;
+            }
+            mg += sign * bonus.0;
+            eg += sign * bonus.1;
+        }
+    }
+    (mg, eg)
+}
+
+/// The midgame penalty for the holes in the pawn shield of `color`'s king:
+/// the files around it without a pawn one or two rows in front of it.
+pub fn get_shield_penalty(board: &Board, color: PieceColor) -> i32 {
+    let king = match board.get_location_from_piece(Piece::new(King, color)) {
+        Some(location) => location,
+        None => return 0,
+    };
+    let forward = get_forward(color);
+    let (row, col) = (king.row as isize, king.col as isize);
+    let mut penalty = 0;
+    for file in col - 1..=col + 1 {
+        if file < 0 || file >= board.size as isize {
+            continue;
+        }
+        if has_pawn(board, color, row + forward, file) {
+            continue;
+        }
+        penalty += if has_pawn(board, color, row + 2 * forward, file) {
+            SHIELD_ADVANCED
+        } else {
+            SHIELD_MISSING
+        };
+    }
+    penalty
+}
+
+/// The midgame penalty for the half-open and open files on and next to the
+/// file of `color`'s king, along which the enemy heavy pieces come in.
+pub fn get_open_file_penalty(board: &Board, color: PieceColor) -> i32 {
+    let king = match board.get_location_from_piece(Piece::new(King, color)) {
+        Some(location) => location,
+        None => return 0,
+    };
+    let rows = 0..board.size as isize;
+    let col = king.col as isize;
+    let mut penalty = 0;
+    for file in col - 1..=col + 1 {
+        if file < 0 || file >= board.size as isize {
+            continue;
+        }
+        if has_pawn_in_rows(board, color, file, rows.clone()) {
+            continue;
+        }
+        penalty += if has_pawn_in_rows(board, opposite_color(color), file, rows.clone()) {
+            SEMI_OPEN_FILE
+        } else {
+            OPEN_FILE
+        };
+    }
+    penalty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pawns(fen: &str) -> PawnEntry {
+        evaluate_pawns(&Board::from_fen(fen).unwrap())
+    }
+
+    #[test]
+    fn weak_pawns_are_penalised() {
+        // Doubled and isolated on the c-file against a healthy chain.
+        let weak = pawns("4k3/5ppp/8/8/8/2P5/2P5/4K3 w - - 0 1");
+        assert!(weak.mg < 0 && weak.eg < 0);
+        // The d-pawn cannot be supported and e4 guards its stop square.
+        let backward = pawns("4k3/8/8/8/4p3/2P5/3P4/4K3 w - - 0 1");
+        let supported = pawns("4k3/8/8/8/4p3/3P4/2P5/4K3 w - - 0 1");
+        assert!(backward.mg < supported.mg);
+        let phalanx = pawns("4k3/8/8/8/3PP3/8/8/4K3 w - - 0 1");
+        let apart = pawns("4k3/8/8/8/3P4/4P3/8/4K3 w - - 0 1");
+        assert!(phalanx.mg > apart.mg);
+    }
+    #[test]
+    fn passed_pawns_are_found_and_grow_with_rank() {
+        let entry = pawns("4k3/8/1P6/8/8/p7/8/4K3 w - - 0 1");
+        assert_eq!(entry.passed[0][1], Some(2));
+        assert_eq!(entry.passed[1][0], Some(5));
+        assert!(pawns("4k3/8/8/8/8/8/8/4K3 w - - 0 1")
+            .passed
+            .iter()
+            .flatten()
+            .all(Option::is_none));
+        assert_eq!(
+            pawns("4k3/2p5/8/1P6/8/8/8/4K3 w - - 0 1").passed[0][1],
+            None
+        );
+
+        let bonus = |fen: &str| {
+            let board = Board::from_fen(fen).unwrap();
+            get_passed_bonus(&board, &evaluate_pawns(&board))
+        };
+        let far = bonus("4k3/8/1P6/8/8/8/8/4K3 w - - 0 1");
+        let near = bonus("4k3/8/8/8/8/1P6/8/4K3 w - - 0 1");
+        let blockaded = bonus("4k3/1n6/1P6/8/8/8/8/4K3 w - - 0 1");
+        assert!(far.1 > near.1);
+        assert!(blockaded.1 < far.1);
+        // Pawns stuck on the far rows have no stop square to look at.
+        assert_eq!(bonus("P3k3/8/8/8/8/8/8/4K2p w - - 0 1"), (0, 0));
+        evaluate(&Board::from_fen("P3k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap());
+    }
+    #[test]
+    fn pawn_shield_holes() {
+        let shield = |fen: &str| get_shield_penalty(&Board::from_fen(fen).unwrap(), First);
+        assert_eq!(shield("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1"), 0);
+        assert_eq!(
+            shield("4k3/8/8/8/8/6P1/5P1P/6K1 w - - 0 1"),
+            SHIELD_ADVANCED
+        );
+        assert_eq!(shield("4k3/8/8/8/8/8/5P1P/6K1 w - - 0 1"), SHIELD_MISSING);
+    }
+    #[test]
+    fn open_files_next_to_the_king() {
+        let files = |fen: &str| get_open_file_penalty(&Board::from_fen(fen).unwrap(), First);
+        assert_eq!(files("4k3/6p1/8/8/8/8/5P1P/6K1 w - - 0 1"), SEMI_OPEN_FILE);
+        assert_eq!(files("4k3/8/8/8/8/8/5P1P/6K1 w - - 0 1"), OPEN_FILE);
+        assert_eq!(files("4k3/8/8/8/8/8/8/K7 w - - 0 1"), 2 * OPEN_FILE);
+    }
+    #[test]
+    fn table_caches_by_pawn_hash() {
+        let mut table = PawnTable::new(1);
+        let board = Board::from_fen("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1").unwrap();
+        assert!(table.probe(board.pawn_hash()).is_none());
+        let entry = table.get(&board);
+        assert_eq!(table.probe(board.pawn_hash()), Some(entry));
+        // Moving a king keeps the pawn hash.
+        let moved = Board::from_fen("3k4/8/8/8/8/2P5/2P5/3K4 w - - 0 1").unwrap();
+        assert_eq!(moved.pawn_hash(), board.pawn_hash());
+        assert_ne!(moved.hash(), board.hash());
+    }
+}
@@ -0,0 +1,132 @@
+use crate::utils::*;
+
+/// Reference positions with their published perft node counts, indexed by
+/// depth starting at 1.
+pub const PERFT_POSITIONS: [(&str, &str, &[u64]); 6] = [
+    (
+        "initial",
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        &[20, 400, 8902, 197281, 4865609],
+    ),
+    (
+        "kiwipete",
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        &[48, 2039, 97862, 4085603],
+    ),
+    (
+        "position 3",
+        "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        &[14, 191, 2812, 43238, 674624],
+    ),
+    (
+        "position 4",
+        "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        &[6, 264, 9467, 422333],
+    ),
+    (
+        "position 5",
+        "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+        &[44, 1486, 62379, 2103487],
+    ),
+    (
+        "position 6",
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        &[46, 2079, 89890, 3894594],
+    ),
+];
+
+/// Counts the leaf nodes of the legal move tree `depth` plies deep.
+pub fn perft(board: &mut Board, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let actions = board.get_all_valid_actions();
+    if depth == 1 {
+        return actions.len() as u64;
+    }
+    let mut nodes = 0;
+    for action in actions {
+        board.make_move(action);
+        nodes += perft(board, depth - 1);
+        board.unmake_move();
+    }
+    nodes
+}
+
+/// Splits the perft count by root move, which is what you compare against
+/// another engine to find the move a generator bug hides under.
+pub fn divide(board: &mut Board, depth: usize) -> Vec<(Action, u64)> {
+    let mut counts = Vec::new();
+    if depth == 0 {
+        return counts;
+    }
+    for action in board.get_all_valid_actions() {
+        board.make_move(action);
+        counts.push((action, perft(board, depth - 1)));
+        board.unmake_move();
+    }
+    counts
+}
+
+pub fn print_divide(board: &mut Board, depth: usize) {
+    let size = board.size;
+    let counts = divide(board, depth);
+    let mut total = 0;
+    for (action, nodes) in counts.iter() {
+        println!("{}: {}", action_to_uci(*action, size), nodes);
+        total += nodes;
+    }
+    println!("\nNodes searched: {}", total);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_position(index: usize, max_depth: usize) {
+        let (name, fen, counts) = PERFT_POSITIONS[index];
+        let mut board = Board::from_fen(fen).unwrap();
+        for (depth, &expected) in counts.iter().enumerate().take(max_depth) {
+            assert_eq!(
+                perft(&mut board, depth + 1),
+                expected,
+                "{} at depth {}",
+                name,
+                depth + 1
+            );
+        }
+        assert_eq!(board.get_fen(), fen);
+    }
+
+    #[test]
+    fn perft_initial() {
+        check_position(0, 4);
+    }
+    #[test]
+    fn perft_kiwipete() {
+        check_position(1, 3);
+    }
+    #[test]
+    fn perft_position_3() {
+        check_position(2, 5);
+    }
+    #[test]
+    fn perft_position_4() {
+        check_position(3, 3);
+    }
+    #[test]
+    fn perft_position_5() {
+        check_position(4, 3);
+    }
+    #[test]
+    fn perft_position_6() {
+        check_position(5, 3);
+    }
+    #[test]
+    fn divide_sums_to_perft() {
+        let mut board = Board::from_fen(PERFT_POSITIONS[1].1).unwrap();
+        let counts = divide(&mut board, 2);
+        assert_eq!(counts.len(), 48);
+        assert_eq!(counts.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+    }
+}
@@ -0,0 +1,727 @@
+use crate::notation::*;
+use crate::utils::PieceColor::*;
+use crate::utils::*;
+use crate::variant::*;
+use std::fmt;
+use std::io::{self, BufRead};
+
+const LINE_WIDTH: usize = 80;
+
+#[derive(Debug, PartialEq)]
+pub enum PgnError {
+    Io(String),
+    UnterminatedTag,
+    UnterminatedComment,
+    InvalidTag,
+    UnbalancedVariation,
+    InvalidFen(FenError),
+    InvalidMove(String),
+    IllegalMove(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameResult {
+    FirstWins,
+    SecondWins,
+    Draw,
+    Ongoing,
+}
+
+/// A move of the movetext together with its annotations and the
+/// alternatives to it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveNode {
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comments_before: Vec<String>,
+    pub comments: Vec<String>,
+    pub variations: Vec<Vec<MoveNode>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Game {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<MoveNode>,
+    pub result: GameResult,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Move(String),
+    Result(GameResult),
+}
+
+impl GameResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameResult::FirstWins => "1-0",
+            GameResult::SecondWins => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Ongoing => "*",
+        }
+    }
+    pub fn parse(input: &str) -> Option<GameResult> {
+        match input {
+            "1-0" => Some(GameResult::FirstWins),
+            "0-1" => Some(GameResult::SecondWins),
+            "1/2-1/2" => Some(GameResult::Draw),
+            "*" => Some(GameResult::Ongoing),
+            _ => None,
+        }
+    }
+}
+
+impl MoveNode {
+    pub fn new(san: &str) -> Self {
+        MoveNode {
+            san: san.to_string(),
+            nags: Vec::new(),
+            comments_before: Vec::new(),
+            comments: Vec::new(),
+            variations: Vec::new(),
+        }
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game {
+    /// Creates an empty game carrying the seven tag roster.
+    pub fn new() -> Self {
+        let tags = [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", "*"),
+        ];
+        Game {
+            tags: tags
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            moves: Vec::new(),
+            result: GameResult::Ongoing,
+        }
+    }
+    /// Records the moves played on `board` since its position was set.
+    pub fn from_board(board: &Board) -> Self {
+        let mut start = board.clone();
+        while start.unmake_move().is_some() {}
+
+        let mut game = Game::new();
+        let fen = start.get_fen();
+        if start.variant != Variant::Standard {
+            game.set_tag("Variant", start.variant.get_name());
+        } else if start.chess960 {
+            game.set_tag("Variant", "Chess960");
+        }
+        if fen != START_FEN || start.chess960 {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+        for action in board.action_list.iter() {
+            game.moves
+                .push(MoveNode::new(&action_to_san(&mut start, *action)));
+            start.make_move(*action);
+        }
+        game.result = match start.get_outcome() {
+            Some(Outcome::Win(First)) => GameResult::FirstWins,
+            Some(Outcome::Win(Second)) => GameResult::SecondWins,
+            Some(Outcome::Draw(_)) => GameResult::Draw,
+            None => GameResult::Ongoing,
+        };
+        game.set_tag("Result", game.result.as_str());
+        game
+    }
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(tag, _)| tag == name) {
+            Some((_, old)) => *old = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+    /// The position the game starts from, taken from the `FEN` tag if present.
+    pub fn get_start_board(&self) -> Result<Board, PgnError> {
+        let fen = self.get_tag("FEN").unwrap_or(START_FEN);
+        let mut board = Board::from_fen(fen).map_err(PgnError::InvalidFen)?;
+        if let Some(variant) = self.get_tag("Variant") {
+            board.chess960 |= variant.eq_ignore_ascii_case("chess960");
+            if let Some(variant) = Variant::from_name(variant) {
+                board.set_variant(variant);
+            }
+        }
+        Ok(board)
+    }
+    /// Replays the main line and returns the final position.
+    pub fn get_board(&self) -> Result<Board, PgnError> {
+        let mut board = self.get_start_board()?;
+        for node in self.moves.iter() {
+            let action = san_to_action(&mut board, &node.san)
+                .map_err(|_| PgnError::IllegalMove(node.san.clone()))?;
+            board.make_move(action);
+        }
+        Ok(board)
+    }
+    pub fn get_actions(&self) -> Result<Vec<Action>, PgnError> {
+        Ok(self.get_board()?.action_list)
+    }
+    fn get_start_ply(&self) -> usize {
+        match self.get_start_board() {
+            Ok(board) => {
+                let turn = if board.turn == First { 0 } else { 1 };
+                (board.fullmove_number - 1) * 2 + turn
+            }
+            Err(_) => 0,
+        }
+    }
+}
+
+/// Reads games one at a time from PGN text, so that large databases never
+/// have to be held in memory at once.
+pub struct PgnReader<R: BufRead> {
+    lines: io::Lines<R>,
+    pending: Option<String>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        PgnReader {
+            lines: reader.lines(),
+            pending: None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = Result<Game, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut text = String::new();
+        let mut in_movetext = false;
+        let mut depths = (0, 0);
+        loop {
+            let line = match self.pending.take() {
+                Some(line) => line,
+                None => match self.lines.next() {
+                    Some(Ok(line)) => line,
+                    Some(Err(error)) => return Some(Err(PgnError::Io(error.to_string()))),
+                    None => break,
+                },
+            };
+            let trimmed = line.trim_start();
+            if depths == (0, 0) && trimmed.starts_with('[') {
+                if in_movetext {
+                    self.pending = Some(line);
+                    break;
+                }
+                text.push_str(&line);
+                text.push('\n');
+                continue;
+            }
+            if depths.0 == 0 && !trimmed.is_empty() {
+                in_movetext = true;
+            }
+            // The game ends at its result, and whatever follows on the line
+            // belongs to the next one.
+            if let Some(end) = find_result_end(&line, &mut depths) {
+                text.push_str(&line[..end]);
+                text.push('\n');
+                if !line[end..].trim().is_empty() {
+                    self.pending = Some(line[end..].to_string());
+                }
+                break;
+            }
+            text.push_str(&line);
+            text.push('\n');
+        }
+        if text.trim().is_empty() {
+            return None;
+        }
+        Some(parse_game(&text))
+    }
+}
+
+/// Scans a line of movetext for a game result outside comments and
+/// variations and returns where it ends. `depths` holds the comment and
+/// variation nesting carried over from the lines before.
+fn find_result_end(line: &str, depths: &mut (usize, usize)) -> Option<usize> {
+    let (comments, variations) = depths;
+    let mut word_start = None;
+    let ends = line
+        .char_indices()
+        .chain(std::iter::once((line.len(), ' ')));
+    for (index, char) in ends {
+        if *comments > 0 {
+            match char {
+                '{' => *comments += 1,
+                '}' => *comments -= 1,
+                _ => {}
+            }
+            continue;
+        }
+        let is_word = !char.is_whitespace() && !"{};()".contains(char);
+        if is_word {
+            word_start.get_or_insert(index);
+            continue;
+        }
+        if let Some(start) = word_start.take() {
+            if *variations == 0 && GameResult::parse(&line[start..index]).is_some() {
+                return Some(index);
+            }
+        }
+        match char {
+            '{' => *comments += 1,
+            '(' => *variations += 1,
+            ')' => *variations = variations.saturating_sub(1),
+            ';' => break,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Parses every game in `text`.
+pub fn parse_games(text: &str) -> Result<Vec<Game>, PgnError> {
+    PgnReader::new(text.as_bytes()).collect()
+}
+
+/// Parses a single game: its tag pairs followed by its movetext.
+pub fn parse_game(text: &str) -> Result<Game, PgnError> {
+    let mut game = Game {
+        tags: Vec::new(),
+        moves: Vec::new(),
+        result: GameResult::Ongoing,
+    };
+    let mut lines: Vec<Vec<MoveNode>> = vec![Vec::new()];
+    let mut pending_comments = Vec::new();
+
+    for token in tokenize(text)? {
+        match token {
+            Token::Tag(name, value) => game.tags.push((name, value)),
+            Token::Comment(comment) => match lines.last_mut().unwrap().last_mut() {
+                Some(node) if pending_comments.is_empty() => node.comments.push(comment),
+                _ => pending_comments.push(comment),
+            },
+            Token::Nag(nag) => {
+                if let Some(node) = lines.last_mut().unwrap().last_mut() {
+                    node.nags.push(nag);
+                }
+            }
+            Token::Move(san) => {
+                let mut node = MoveNode::new(&san);
+                node.comments_before = std::mem::take(&mut pending_comments);
+                lines.last_mut().unwrap().push(node);
+            }
+            Token::Open => {
+                if lines.last().unwrap().is_empty() {
+                    return Err(PgnError::UnbalancedVariation);
+                }
+                lines.push(Vec::new());
+            }
+            Token::Close => {
+                if lines.len() < 2 {
+                    return Err(PgnError::UnbalancedVariation);
+                }
+                let mut variation = lines.pop().unwrap();
+                if let Some(node) = variation.last_mut() {
+                    node.comments.append(&mut pending_comments);
+                }
+                let parent = lines.last_mut().unwrap().last_mut().unwrap();
+                parent.variations.push(variation);
+            }
+            Token::Result(result) => game.result = result,
+        }
+    }
+    if lines.len() != 1 {
+        return Err(PgnError::UnbalancedVariation);
+    }
+    game.moves = lines.pop().unwrap();
+    if let Some(node) = game.moves.last_mut() {
+        node.comments.append(&mut pending_comments);
+    }
+    if game.result == GameResult::Ongoing {
+        if let Some(result) = game.get_tag("Result").and_then(GameResult::parse) {
+            game.result = result;
+        }
+    }
+    Ok(game)
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, PgnError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\n' => {
+                line_start = true;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '%' if line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            ';' => {
+                let comment: String = chars.by_ref().take_while(|&c| c != '\n').collect();
+                tokens.push(Token::Comment(comment.trim().to_string()));
+            }
+            '{' => {
+                let mut comment = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    comment.push(c);
+                }
+                if !closed {
+                    return Err(PgnError::UnterminatedComment);
+                }
+                let comment: Vec<&str> = comment.split_whitespace().collect();
+                tokens.push(Token::Comment(comment.join(" ")));
+            }
+            '[' => {
+                let mut tag = String::new();
+                let mut in_string = false;
+                let mut escaped = false;
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if !in_string && c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    if in_string && !escaped && c == '\\' {
+                        escaped = true;
+                    } else {
+                        if c == '"' && !escaped {
+                            in_string = !in_string;
+                        }
+                        escaped = false;
+                    }
+                    tag.push(c);
+                }
+                if !closed {
+                    return Err(PgnError::UnterminatedTag);
+                }
+                tokens.push(parse_tag(&tag)?);
+            }
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '$' => {
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    digits.push(c);
+                    chars.next();
+                }
+                let nag = digits
+                    .parse()
+                    .map_err(|_| PgnError::InvalidMove(format!("${}", digits)))?;
+                tokens.push(Token::Nag(nag));
+            }
+            _ => {
+                let mut symbol = char.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{}();[$".contains(c) {
+                        break;
+                    }
+                    symbol.push(c);
+                    chars.next();
+                }
+                push_symbol(&mut tokens, &symbol)?;
+            }
+        }
+        line_start = false;
+    }
+    Ok(tokens)
+}
+
+fn parse_tag(tag: &str) -> Result<Token, PgnError> {
+    let tag = tag.trim();
+    let (name, value) = tag
+        .split_once(char::is_whitespace)
+        .ok_or(PgnError::InvalidTag)?;
+    let value = value.trim();
+    if !value.starts_with('"') || !value.ends_with('"') || value.len() < 2 {
+        return Err(PgnError::InvalidTag);
+    }
+    let value = value[1..value.len() - 1]
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\");
+    Ok(Token::Tag(name.to_string(), value))
+}
+
+/// Splits a movetext symbol such as `12.Nf3!?` into its move number, move
+/// and annotation glyph.
+fn push_symbol(tokens: &mut Vec<Token>, symbol: &str) -> Result<(), PgnError> {
+    if let Some(result) = GameResult::parse(symbol) {
+        tokens.push(Token::Result(result));
+        return Ok(());
+    }
+    // Digits are only a move number when dots follow, as `0-0` castles.
+    let unnumbered = symbol.trim_start_matches(|c: char| c.is_ascii_digit());
+    let symbol = if unnumbered.is_empty() || unnumbered.starts_with('.') {
+        unnumbered.trim_start_matches('.')
+    } else {
+        symbol
+    };
+    if symbol.is_empty() {
+        return Ok(());
+    }
+
+    let san = symbol.trim_end_matches(['!', '?']);
+    if san.is_empty() {
+        return Err(PgnError::InvalidMove(symbol.to_string()));
+    }
+    tokens.push(Token::Move(san.to_string()));
+    let nag = match &symbol[san.len()..] {
+        "" => None,
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        other => return Err(PgnError::InvalidMove(format!("{}{}", san, other))),
+    };
+    if let Some(nag) = nag {
+        tokens.push(Token::Nag(nag));
+    }
+    Ok(())
+}
+
+fn push_movetext(words: &mut Vec<String>, moves: &[MoveNode], ply: usize) {
+    let mut needs_number = true;
+    for (i, node) in moves.iter().enumerate() {
+        let ply = ply + i;
+        for comment in node.comments_before.iter() {
+            words.push(format!("{{{}}}", comment));
+            needs_number = true;
+        }
+        if ply.is_multiple_of(2) {
+            words.push(format!("{}.", ply / 2 + 1));
+        } else if needs_number {
+            words.push(format!("{}...", ply / 2 + 1));
+        }
+        words.push(node.san.clone());
+        needs_number = false;
+        for nag in node.nags.iter() {
+            words.push(format!("${}", nag));
+        }
+        for comment in node.comments.iter() {
+            words.push(format!("{{{}}}", comment));
+            needs_number = true;
+        }
+        for variation in node.variations.iter() {
+            let start = words.len();
+            push_movetext(words, variation, ply);
+            if words.len() > start {
+                words[start].insert(0, '(');
+                words.last_mut().unwrap().push(')');
+            }
+            needs_number = true;
+        }
+    }
+}
+
+impl fmt::Display for Game {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, value) in self.tags.iter() {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+
+        let mut words = Vec::new();
+        push_movetext(&mut words, &self.moves, self.get_start_ply());
+        words.push(self.result.as_str().to_string());
+
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.len() + 1 + word.len() > LINE_WIDTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&word);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ANNOTATED: &str = r#"[Event "Casual \"blitz\""]
+[Site "?"]
+[Result "1-0"]
+
+{Opening comment} 1. e4 e5 2. Nf3 Nc6 (2... d6 {Philidor} 3. d4 (3. Bc4) 3... Nf6)
+3. Bb5!? $14 a6 ; the main line
+4. Ba4 Nf6 5. O-O 1-0
+"#;
+
+    #[test]
+    fn parses_tags_comments_nags_and_variations() {
+        let game = parse_game(ANNOTATED).unwrap();
+        assert_eq!(game.get_tag("Event"), Some("Casual \"blitz\""));
+        assert_eq!(game.result, GameResult::FirstWins);
+        assert_eq!(game.moves.len(), 9);
+        assert_eq!(game.moves[0].comments_before, vec!["Opening comment"]);
+
+        let variation = &game.moves[3].variations[0];
+        assert_eq!(variation[0].san, "d6");
+        assert_eq!(variation[0].comments, vec!["Philidor"]);
+        assert_eq!(variation[1].variations[0][0].san, "Bc4");
+
+        assert_eq!(game.moves[4].nags, vec![5, 14]);
+        assert_eq!(game.moves[5].comments, vec!["the main line"]);
+
+        let board = game.get_board().unwrap();
+        assert_eq!(
+            board.get_fen(),
+            "r1bqkb1r/1ppp1ppp/p1n2n2/4p3/B3P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 3 5"
+        );
+    }
+    #[test]
+    fn writes_what_it_reads() {
+        let game = parse_game(ANNOTATED).unwrap();
+        let text = game.to_string();
+        assert!(text.lines().all(|line| line.len() <= LINE_WIDTH));
+        let movetext = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert!(movetext
+            .contains("2. Nf3 Nc6 (2... d6 {Philidor} 3. d4 (3. Bc4) 3... Nf6) 3. Bb5 $5 $14"));
+        assert_eq!(parse_game(&text).unwrap(), game);
+    }
+    #[test]
+    fn streams_multiple_games() {
+        let text = "[Event \"one\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n\n[Event \"two\"]\n\n1. d4 *\n";
+        let games: Vec<Game> = PgnReader::new(text.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].result, GameResult::SecondWins);
+        assert_eq!(games[1].get_tag("Event"), Some("two"));
+        assert_eq!(games[1].moves.len(), 1);
+    }
+    #[test]
+    fn splits_games_without_tags_at_their_results() {
+        let text =
+            "1. e4 {not over: 1-0} e5 (1... c5 0-1) 1-0\n1. d4 d5 *\n\n1. c4 0-1 1. Nf3 1/2-1/2\n";
+        let games = parse_games(text).unwrap();
+        let results: Vec<GameResult> = games.iter().map(|game| game.result).collect();
+        assert_eq!(
+            results,
+            [
+                GameResult::FirstWins,
+                GameResult::Ongoing,
+                GameResult::SecondWins,
+                GameResult::Draw
+            ]
+        );
+        let lengths: Vec<usize> = games.iter().map(|game| game.moves.len()).collect();
+        assert_eq!(lengths, [2, 2, 1, 1]);
+    }
+    #[test]
+    fn reads_castling_with_zeros() {
+        let text = "1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4.0-0 d6 5. d3 Be6 6. h3 Qd7 7. Nc3 0-0-0 *";
+        let board = parse_game(text).unwrap().get_board().unwrap();
+        assert_eq!(
+            board.get_fen(),
+            "2kr2nr/pppq1ppp/2npb3/2b1p3/2B1P3/2NP1N1P/PPP2PP1/R1BQ1RK1 w - - 3 8"
+        );
+    }
+    #[test]
+    fn records_games_from_a_board() {
+        let fen = "7k/1p4pp/8/8/8/8/8/4R1K1 b - - 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        for san in ["b6", "Re8#"] {
+            let action = san_to_action(&mut board, san).unwrap();
+            board.commit_move(action).unwrap();
+        }
+        let game = Game::from_board(&board);
+        assert_eq!(game.get_tag("FEN"), Some(fen));
+        assert_eq!(game.result, GameResult::FirstWins);
+        let text = game.to_string();
+        assert!(text.ends_with("1... b6 2. Re8# 1-0\n"));
+        assert_eq!(
+            parse_game(&text).unwrap().get_board().unwrap().get_fen(),
+            board.get_fen()
+        );
+    }
+    #[test]
+    fn records_chess960_games() {
+        let mut board = Board::new_chess960(518).unwrap();
+        for san in ["Nf3", "Nf6", "g3", "g6", "Bg2", "Bg7", "O-O", "O-O"] {
+            let action = san_to_action(&mut board, san).unwrap();
+            board.commit_move(action).unwrap();
+        }
+        let game = Game::from_board(&board);
+        assert_eq!(game.get_tag("Variant"), Some("Chess960"));
+        assert_eq!(game.get_tag("FEN"), Some(START_FEN));
+        let replayed = parse_game(&game.to_string()).unwrap().get_board().unwrap();
+        assert!(replayed.chess960);
+        assert_eq!(replayed.get_fen(), board.get_fen());
+    }
+    #[test]
+    fn records_variant_games() {
+        let mut board = Board::from_fen("4k3/3b4/8/8/8/8/8/3RK3 w - - 0 1").unwrap();
+        board.set_variant(Variant::Atomic);
+        let action = san_to_action(&mut board, "Rxd7").unwrap();
+        board.commit_move(action).unwrap();
+        let game = Game::from_board(&board);
+        assert_eq!(game.get_tag("Variant"), Some("Atomic"));
+        assert_eq!(game.result, GameResult::FirstWins);
+        let replayed = parse_game(&game.to_string()).unwrap().get_board().unwrap();
+        assert_eq!(replayed.variant, Variant::Atomic);
+        assert_eq!(replayed.get_fen(), board.get_fen());
+    }
+    #[test]
+    fn reports_malformed_input() {
+        assert_eq!(
+            parse_game("1. e4 {oops"),
+            Err(PgnError::UnterminatedComment)
+        );
+        assert_eq!(
+            parse_game("1. e4 (1... e5"),
+            Err(PgnError::UnbalancedVariation)
+        );
+        assert_eq!(
+            parse_game("[Event \"x\"\n1. e4"),
+            Err(PgnError::UnterminatedTag)
+        );
+        let game = parse_game("1. e4 e4").unwrap();
+        assert_eq!(
+            game.get_board().unwrap_err(),
+            PgnError::IllegalMove("e4".to_string())
+        );
+    }
+}
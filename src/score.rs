@@ -0,0 +1,87 @@
+/// A search value in centipawns. Values beyond `MATE_BOUND` stand for a
+/// forced mate, `MATE` minus the number of plies it takes from the root.
+pub type Score = i32;
+
+/// The value of mating right at the root. A mate `ply` plies away is
+/// worth `MATE - ply`, so shorter mates score higher.
+pub const MATE: Score = 32_000;
+/// Above every value a search can return, for the initial window.
+pub const INFINITY: Score = MATE + 1;
+/// Values at least this far from zero are mates.
+pub const MATE_BOUND: Score = MATE - 1_000;
+
+/// The value of mating the opponent `ply` plies from the root.
+pub fn mate_in(ply: usize) -> Score {
+    MATE - ply as Score
+}
+/// The value of being mated `ply` plies from the root.
+pub fn mated_in(ply: usize) -> Score {
+    -MATE + ply as Score
+}
+pub fn is_mate(score: Score) -> bool {
+    score.abs() >= MATE_BOUND
+}
+
+/// Turns a mate value relative to the root into one relative to the node
+/// at `ply`, which is how the transposition table keeps them, as the same
+/// position may come up at another distance from the root.
+pub fn score_to_tt(score: Score, ply: usize) -> Score {
+    if score >= MATE_BOUND {
+        score + ply as Score
+    } else if score <= -MATE_BOUND {
+        score - ply as Score
+    } else {
+        score
+    }
+}
+/// Turns a value from the transposition table back into one relative to
+/// the root, for a node at `ply`.
+pub fn score_from_tt(score: Score, ply: usize) -> Score {
+    if score >= MATE_BOUND {
+        score - ply as Score
+    } else if score <= -MATE_BOUND {
+        score + ply as Score
+    } else {
+        score
+    }
+}
+
+/// Formats a score as UCI does: `cp 35`, or `mate 3` for a mate in three
+/// moves and `mate -3` for being mated in three.
+pub fn format_score(score: Score) -> String {
+    if score >= MATE_BOUND {
+        format!("mate {}", (MATE - score + 1) / 2)
+    } else if score <= -MATE_BOUND {
+        format!("mate -{}", (MATE + score) / 2)
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mates_are_counted_in_moves() {
+        assert_eq!(format_score(35), "cp 35");
+        assert_eq!(format_score(-120), "cp -120");
+        assert_eq!(format_score(mate_in(1)), "mate 1");
+        assert_eq!(format_score(mate_in(5)), "mate 3");
+        assert_eq!(format_score(mated_in(2)), "mate -1");
+        assert_eq!(format_score(mated_in(6)), "mate -3");
+        assert!(mate_in(1) > mate_in(3));
+        assert!(mated_in(1) < mated_in(3));
+        assert!(is_mate(mated_in(40)) && !is_mate(2_000));
+    }
+    #[test]
+    fn table_scores_are_relative_to_the_node() {
+        // A mate three plies below a node five plies deep is eight plies
+        // from this root and three from any other.
+        let stored = score_to_tt(mate_in(8), 5);
+        assert_eq!(stored, mate_in(3));
+        assert_eq!(score_from_tt(stored, 2), mate_in(5));
+        assert_eq!(score_from_tt(score_to_tt(mated_in(8), 5), 2), mated_in(5));
+        assert_eq!(score_from_tt(score_to_tt(150, 5), 2), 150);
+    }
+}
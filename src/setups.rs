@@ -0,0 +1,150 @@
+use crate::utils::*;
+
+/// The built in starting positions, from the standard game down to the
+/// small boards that are cheap enough to search exhaustively.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setup {
+    Standard,
+    /// 6x6 without bishops, castling or pawn double steps.
+    LosAlamos,
+    /// 5x5 minichess with the standard pieces on each back rank.
+    Gardner,
+    /// 10x10 with an extra knight on each side of the standard back rank.
+    TenByTen,
+}
+
+pub const SETUPS: [Setup; 4] = [
+    Setup::Standard,
+    Setup::LosAlamos,
+    Setup::Gardner,
+    Setup::TenByTen,
+];
+
+impl Setup {
+    pub fn get_fen(&self) -> &'static str {
+        match self {
+            Setup::Standard => START_FEN,
+            Setup::LosAlamos => "rnqknr/pppppp/6/6/PPPPPP/RNQKNR w - - 0 1",
+            Setup::Gardner => "rnbqk/ppppp/5/PPPPP/RNBQK w - - 0 1",
+            Setup::TenByTen => {
+                "rnnbqkbnnr/pppppppppp/10/10/10/10/10/10/PPPPPPPPPP/RNNBQKBNNR w KQkq - 0 1"
+            }
+        }
+    }
+    /// The rules of the setup, which follow from its size alone so that
+    /// they survive a round trip through FEN.
+    pub fn get_rules(&self) -> Rules {
+        Rules::new(self.get_fen().split('/').count())
+    }
+}
+
+impl Board {
+    pub fn from_setup(setup: Setup) -> Board {
+        let mut board = Board::from_fen(setup.get_fen()).unwrap();
+        board.rules = setup.get_rules();
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::*;
+    use crate::eval::ClassicalEvaluator;
+    use crate::notation::*;
+    use crate::perft::perft;
+    use crate::tt::TranspositionTable;
+    use crate::utils::PieceKind::*;
+    use crate::variant::Variant;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn setups_round_trip() {
+        for setup in SETUPS {
+            let board = Board::from_setup(setup);
+            assert_eq!(board.get_fen(), setup.get_fen());
+        }
+        assert_eq!(Board::from_setup(Setup::TenByTen).size, 10);
+
+        // Boards are as large as the hash keys go and no larger.
+        let fen = |size: usize| {
+            let empty = format!("{}/", size).repeat(size - 2);
+            format!("k{}/{}{}K w - - 0 1", size - 1, empty, size - 1)
+        };
+        assert_eq!(Board::from_fen(&fen(16)).unwrap().size, 16);
+        assert_eq!(Board::from_fen(&fen(17)).err(), Some(FenError::InvalidSize));
+    }
+    #[test]
+    fn squares_beyond_the_eighth_rank() {
+        let mut board = Board::from_setup(Setup::TenByTen);
+        let j10 = Location { row: 0, col: 9 };
+        assert_eq!(algebraic_to_location("j10", 10).unwrap(), j10);
+        assert_eq!(location_to_algebraic(j10, 10), "j10");
+        assert!(algebraic_to_location("k1", 10).is_err());
+        assert!(algebraic_to_location("a11", 10).is_err());
+
+        for (uci, san) in [
+            ("e2e4", "e4"),
+            ("e9e7", "e7"),
+            ("c1d3", "Nd3"),
+            ("h10g8", "Ng8"),
+        ] {
+            let action = board.get_action_from_uci(uci).unwrap();
+            assert_eq!(action_to_uci(action, board.size), uci);
+            assert_eq!(action_to_san(&mut board, action), san);
+            board.commit_move(action).unwrap();
+        }
+        assert_eq!(
+            board.get_fen(),
+            "rnnbqkb1nr/pppp1ppppp/6n3/4p5/10/10/4P5/3N6/PPPP1PPPPP/RN1BQKBNNR w KQkq - 2 3"
+        );
+    }
+    #[test]
+    fn small_board_rules() {
+        let mut board = Board::from_setup(Setup::Gardner);
+        // Five pawn pushes, two knight moves and no double steps.
+        assert_eq!(perft(&mut board, 1), 7);
+        let action = board.get_action_from_uci("a2a4").unwrap();
+        assert!(board.test_move(action).is_err());
+
+        let mut board = Board::from_fen("4k1/P5/6/6/6/K5 w - - 0 1").unwrap();
+        board.rules = Setup::LosAlamos.get_rules();
+        let promotions: Vec<String> = board
+            .generate_actions(None)
+            .into_iter()
+            .filter(|action| matches!(action.kind, ActionKind::Promotion(_)))
+            .map(|action| action_to_uci(action, board.size))
+            .collect();
+        assert_eq!(promotions, ["a5a6q", "a5a6r", "a5a6n"]);
+        let action = board.get_action_from_uci("a5a6b").unwrap();
+        assert!(board.test_move(action).is_err());
+
+        // The rules survive FEN and a detour through antichess.
+        let fen = Board::from_setup(Setup::LosAlamos).get_fen();
+        let mut board = Board::from_fen(&fen).unwrap();
+        assert_eq!(board.rules, Setup::LosAlamos.get_rules());
+        board.set_variant(Variant::Antichess);
+        assert!(board.rules.promotion_kinds.contains(&King));
+        board.set_variant(Variant::Standard);
+        assert_eq!(board.rules, Setup::LosAlamos.get_rules());
+    }
+    #[test]
+    fn searches_small_boards() {
+        // Back rank mate on the 6x6 board.
+        let board = Board::from_fen("3k2/2ppp1/6/6/6/R2K2 w - - 0 1").unwrap();
+        let limits = SearchLimits {
+            depth: Some(2),
+            ..SearchLimits::default()
+        };
+        let mut tt = TranspositionTable::new(1);
+        let info = search_iterative(
+            &board,
+            &limits,
+            &mut tt,
+            &mut ClassicalEvaluator::default(),
+            &AtomicBool::new(false),
+            |_| {},
+        );
+        assert_eq!(action_to_uci(info.unwrap().action, 6), "a1a6");
+    }
+}
@@ -0,0 +1,129 @@
+use crate::score::*;
+use crate::utils::*;
+use std::mem::size_of;
+
+pub const DEFAULT_HASH_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TtEntry {
+    pub hash: u64,
+    pub depth: usize,
+    pub bound: Bound,
+    /// Mate values count their plies from this node rather than the root.
+    pub value: Score,
+    pub action: Option<Action>,
+    generation: u8,
+}
+
+/// A fixed-size table of search results indexed by position hash.
+///
+/// A slot is replaced when it holds a result from an earlier search or one
+/// searched no deeper than the new result, so deep results survive the many
+/// shallow ones written during the same search.
+pub struct TranspositionTable {
+    entries: Vec<Option<TtEntry>>,
+    generation: u8,
+}
+
+impl TranspositionTable {
+    /// Creates a table that takes up about `size` megabytes.
+    pub fn new(size: usize) -> Self {
+        let count = (size.max(1) * 1024 * 1024 / size_of::<Option<TtEntry>>()).max(1);
+        TranspositionTable {
+            entries: vec![None; count],
+            generation: 0,
+        }
+    }
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn clear(&mut self) {
+        self.entries.iter_mut().for_each(|entry| *entry = None);
+        self.generation = 0;
+    }
+    /// Marks the entries written so far as stale, so a new search may
+    /// overwrite them regardless of depth.
+    pub fn new_search(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+    }
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.entries.len() as u64) as usize
+    }
+    pub fn probe(&self, hash: u64) -> Option<TtEntry> {
+        self.entries[self.index(hash)].filter(|entry| entry.hash == hash)
+    }
+    pub fn store(
+        &mut self,
+        hash: u64,
+        depth: usize,
+        bound: Bound,
+        value: Score,
+        action: Option<Action>,
+    ) {
+        let index = self.index(hash);
+        let generation = self.generation;
+        if let Some(old) = self.entries[index] {
+            if old.generation == generation && old.depth > depth {
+                return;
+            }
+        }
+        self.entries[index] = Some(TtEntry {
+            hash,
+            depth,
+            bound,
+            value,
+            action,
+            generation,
+        });
+    }
+    /// Share of the table in use, in permille as UCI reports it.
+    pub fn get_hashfull(&self) -> usize {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|entry| entry.is_some_and(|entry| entry.generation == self.generation))
+            .count();
+        used * 1000 / sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_shallow_and_stale_entries() {
+        let mut tt = TranspositionTable::new(1);
+        let hash = 12345;
+        tt.store(hash, 4, Bound::Exact, 150, None);
+        assert_eq!(tt.probe(hash).unwrap().depth, 4);
+        assert!(tt.probe(hash + 1).is_none());
+
+        // A shallower result from the same search keeps the deeper one.
+        tt.store(hash, 2, Bound::Lower, 50, None);
+        assert_eq!(tt.probe(hash).unwrap().depth, 4);
+
+        tt.new_search();
+        tt.store(hash, 2, Bound::Upper, 50, None);
+        let entry = tt.probe(hash).unwrap();
+        assert_eq!((entry.depth, entry.bound), (2, Bound::Upper));
+
+        tt.clear();
+        assert!(tt.probe(hash).is_none());
+    }
+    #[test]
+    fn size_is_given_in_megabytes() {
+        let entry_size = size_of::<Option<TtEntry>>();
+        assert_eq!(
+            TranspositionTable::new(4).capacity(),
+            4 * 1024 * 1024 / entry_size
+        );
+    }
+}
@@ -0,0 +1,461 @@
+use crate::engine::*;
+use crate::eval::*;
+use crate::score::*;
+use crate::tt::*;
+use crate::utils::PieceColor::*;
+use crate::utils::*;
+use crate::variant::*;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+#[derive(Debug)]
+pub enum UciError {
+    MissingPosition,
+    InvalidFen(FenError),
+    InvalidMove(String),
+}
+
+/// The options that weight the optional evaluation terms, in percent.
+const WEIGHT_OPTIONS: [&str; 4] = ["Mobility", "KingAttack", "OpenFiles", "PawnShield"];
+const MAX_WEIGHT: usize = 400;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GoOptions {
+    pub depth: Option<usize>,
+    pub move_time: Option<u64>,
+    pub white_time: Option<u64>,
+    pub black_time: Option<u64>,
+    pub white_increment: Option<u64>,
+    pub black_increment: Option<u64>,
+    pub moves_to_go: Option<u64>,
+    pub infinite: bool,
+}
+
+pub struct Uci {
+    board: Board,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<TranspositionTable>>,
+    infinite: bool,
+    hash_size: usize,
+    chess960: bool,
+    variant: Variant,
+    weights: EvalWeights,
+    tt: Option<TranspositionTable>,
+}
+
+/// Reads UCI commands from stdin until `quit` or end of input.
+pub fn run() {
+    let mut uci = Uci::new();
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !uci.handle_command(&line) {
+            uci.stop_search();
+            return;
+        }
+    }
+    // Let a scripted `go` finish when its input runs out.
+    uci.finish_search();
+}
+
+/// Parses the arguments of a `position` command, for example
+/// `startpos moves e2e4 e7e5` or `fen <fen> moves e1g1`. With `chess960`
+/// castling moves are read as the king taking its own rook, and the moves
+/// follow the rules of `variant`.
+pub fn parse_position(args: &[&str], chess960: bool, variant: Variant) -> Result<Board, UciError> {
+    let moves_index = args.iter().position(|&arg| arg == "moves");
+    let (setup, moves) = match moves_index {
+        Some(index) => (&args[..index], &args[index + 1..]),
+        None => (args, &args[args.len()..]),
+    };
+
+    let mut board = match setup.first() {
+        Some(&"startpos") => Board::from_fen(START_FEN).map_err(UciError::InvalidFen)?,
+        Some(&"fen") => Board::from_fen(&setup[1..].join(" ")).map_err(UciError::InvalidFen)?,
+        _ => return Err(UciError::MissingPosition),
+    };
+    board.chess960 |= chess960;
+    if variant != Variant::Standard {
+        board.set_variant(variant);
+    }
+    for &input in moves {
+        let action = board
+            .get_action_from_uci(input)
+            .map_err(|_| UciError::InvalidMove(input.to_string()))?;
+        board
+            .commit_move(action)
+            .map_err(|_| UciError::InvalidMove(input.to_string()))?;
+    }
+    Ok(board)
+}
+
+/// Parses `name <name> value <value>` from a `setoption` command.
+pub fn parse_setoption(args: &[&str]) -> Option<(String, String)> {
+    let name_index = args.iter().position(|&arg| arg == "name")?;
+    let value_index = args
+        .iter()
+        .position(|&arg| arg == "value")
+        .unwrap_or(args.len());
+    if value_index <= name_index + 1 {
+        return None;
+    }
+    let name = args[name_index + 1..value_index].join(" ");
+    let value = args.get(value_index + 1..).unwrap_or(&[]).join(" ");
+    Some((name, value))
+}
+
+/// Parses the arguments of a `go` command. Unknown tokens are skipped.
+pub fn parse_go(args: &[&str]) -> GoOptions {
+    let mut options = GoOptions::default();
+    let mut args = args.iter();
+    while let Some(&arg) = args.next() {
+        let mut value = || args.next().and_then(|value| value.parse::<u64>().ok());
+        match arg {
+            "depth" => options.depth = value().map(|depth| depth as usize),
+            "movetime" => options.move_time = value(),
+            "wtime" => options.white_time = value(),
+            "btime" => options.black_time = value(),
+            "winc" => options.white_increment = value(),
+            "binc" => options.black_increment = value(),
+            "movestogo" => options.moves_to_go = value(),
+            "infinite" => options.infinite = true,
+            _ => {}
+        }
+    }
+    options
+}
+
+/// Formats a search value, which is from `First`'s point of view, as a
+/// UCI score from the side to move's point of view.
+fn format_uci_score(value: Score, turn: PieceColor) -> String {
+    format_score(if turn == First { value } else { -value })
+}
+
+impl Default for Uci {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Uci {
+    pub fn new() -> Self {
+        Uci {
+            board: Board::from_fen(START_FEN).unwrap(),
+            stop: Arc::new(AtomicBool::new(false)),
+            search: None,
+            infinite: false,
+            hash_size: DEFAULT_HASH_SIZE,
+            chess960: false,
+            variant: Variant::Standard,
+            weights: EvalWeights::default(),
+            tt: Some(TranspositionTable::new(DEFAULT_HASH_SIZE)),
+        }
+    }
+    /// Handles one line of input and returns `false` once the GUI asks to quit.
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match tokens.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return true,
+        };
+        match command {
+            "uci" => {
+                println!("id name contrapunct {}", env!("CARGO_PKG_VERSION"));
+                println!("id author TinkoBinko");
+                println!(
+                    "option name Hash type spin default {} min 1 max 4096",
+                    DEFAULT_HASH_SIZE
+                );
+                for name in WEIGHT_OPTIONS {
+                    println!(
+                        "option name {} type spin default 100 min 0 max {}",
+                        name, MAX_WEIGHT
+                    );
+                }
+                println!("option name UCI_Chess960 type check default false");
+                let names: Vec<String> = VARIANTS
+                    .iter()
+                    .map(|variant| format!("var {}", variant.get_uci_name()))
+                    .collect();
+                println!(
+                    "option name UCI_Variant type combo default chess {}",
+                    names.join(" ")
+                );
+                println!("uciok");
+            }
+            "isready" => println!("readyok"),
+            "ucinewgame" => {
+                self.stop_search();
+                self.board = Board::from_fen(START_FEN).unwrap();
+                self.board.chess960 = self.chess960;
+                self.board.set_variant(self.variant);
+                self.get_tt().clear();
+            }
+            "setoption" => {
+                self.stop_search();
+                match parse_setoption(args) {
+                    Some((name, value)) => self.set_option(&name, &value),
+                    None => println!("info string invalid setoption"),
+                }
+            }
+            "position" => {
+                self.stop_search();
+                match parse_position(args, self.chess960, self.variant) {
+                    Ok(board) => self.board = board,
+                    Err(error) => println!("info string invalid position: {:?}", error),
+                }
+            }
+            "go" => {
+                self.stop_search();
+                self.start_search(parse_go(args));
+            }
+            "stop" => self.stop_search(),
+            "quit" => return false,
+            _ => println!("info string unknown command: {}", command),
+        }
+        true
+    }
+    fn get_tt(&mut self) -> &mut TranspositionTable {
+        let hash_size = self.hash_size;
+        self.tt
+            .get_or_insert_with(|| TranspositionTable::new(hash_size))
+    }
+    fn set_option(&mut self, name: &str, value: &str) {
+        let name = name.to_lowercase();
+        if name == "uci_chess960" {
+            match value.parse::<bool>() {
+                Ok(chess960) => {
+                    self.chess960 = chess960;
+                    self.board.chess960 = chess960;
+                }
+                Err(_) => println!("info string unsupported option: {} {}", name, value),
+            }
+            return;
+        }
+        if name == "uci_variant" {
+            match Variant::from_name(value) {
+                Some(variant) => {
+                    self.variant = variant;
+                    self.board.set_variant(variant);
+                    // The hash leaves the variant out, so old entries would
+                    // be scored by the wrong rules.
+                    self.get_tt().clear();
+                }
+                None => println!("info string unsupported option: {} {}", name, value),
+            }
+            return;
+        }
+        match (name.as_str(), value.parse::<usize>()) {
+            ("hash", Ok(size)) if size > 0 => {
+                self.hash_size = size;
+                self.tt = Some(TranspositionTable::new(size));
+            }
+            (_, Ok(weight)) if weight <= MAX_WEIGHT => match self.get_weight(&name) {
+                Some(slot) => *slot = weight as i32,
+                None => println!("info string unsupported option: {} {}", name, value),
+            },
+            _ => println!("info string unsupported option: {} {}", name, value),
+        }
+    }
+    /// The evaluation weight a lowercased option name stands for.
+    fn get_weight(&mut self, name: &str) -> Option<&mut i32> {
+        match name {
+            "mobility" => Some(&mut self.weights.mobility),
+            "kingattack" => Some(&mut self.weights.king_attack),
+            "openfiles" => Some(&mut self.weights.open_files),
+            "pawnshield" => Some(&mut self.weights.pawn_shield),
+            _ => None,
+        }
+    }
+    fn get_limits(&self, options: &GoOptions) -> SearchLimits {
+        let (time, increment) = if self.board.turn == First {
+            (options.white_time, options.white_increment)
+        } else {
+            (options.black_time, options.black_increment)
+        };
+        // An infinite search ignores every limit and only ends on `stop`.
+        if options.infinite {
+            return SearchLimits::default();
+        }
+        SearchLimits {
+            depth: options.depth,
+            move_time: options.move_time.map(Duration::from_millis),
+            clock: time.map(|time| Clock {
+                remaining: Duration::from_millis(time),
+                increment: Duration::from_millis(increment.unwrap_or(0)),
+                moves_to_go: options.moves_to_go.map(|moves| moves as u32),
+            }),
+        }
+    }
+    fn start_search(&mut self, options: GoOptions) {
+        // Every search gets its own flag, so a `stop` meant for an earlier
+        // search cannot reach this one.
+        self.stop = Arc::new(AtomicBool::new(false));
+        self.infinite = options.infinite;
+        let limits = self.get_limits(&options);
+
+        let board = self.board.clone();
+        let stop = self.stop.clone();
+        let weights = self.weights;
+        self.get_tt();
+        let mut tt = self.tt.take().unwrap();
+        self.search = Some(thread::spawn(move || {
+            let mut evaluator = ClassicalEvaluator::new(weights);
+            let best = search_iterative(&board, &limits, &mut tt, &mut evaluator, &stop, |info| {
+                println!(
+                    "info depth {} score {} nodes {} time {} pv {}",
+                    info.depth,
+                    format_uci_score(info.value, board.turn),
+                    info.nodes,
+                    info.elapsed.as_millis(),
+                    board.get_uci_from_action(info.action)
+                );
+            });
+            println!("info hashfull {}", tt.get_hashfull());
+            // In infinite mode the best move may only be sent after `stop`.
+            while options.infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+
+            let mut board = board;
+            let best_action = best
+                .map(|info| info.action)
+                .or_else(|| board.get_all_valid_actions().first().copied());
+            match best_action {
+                Some(action) => println!("bestmove {}", board.get_uci_from_action(action)),
+                None => println!("bestmove 0000"),
+            }
+            tt
+        }));
+    }
+    pub fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.finish_search();
+    }
+    /// Waits for the running search to report its best move. An infinite
+    /// search is stopped first, since it would otherwise never finish.
+    pub fn finish_search(&mut self) {
+        if self.infinite {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        if let Some(search) = self.search.take() {
+            if let Ok(tt) = search.join() {
+                self.tt = Some(tt);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn position_startpos_with_moves() {
+        let board = parse_position(
+            &["startpos", "moves", "e2e4", "e7e5", "g1f3"],
+            false,
+            Variant::Standard,
+        )
+        .unwrap();
+        assert_eq!(
+            board.get_fen(),
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+        );
+    }
+    #[test]
+    fn position_fen_with_castling_and_promotion() {
+        let args: Vec<&str> = "fen r3k3/1P6/8/8/8/8/8/4K2R w Kq - 0 1 moves e1g1 a8d8 b7b8n"
+            .split_whitespace()
+            .collect();
+        let board = parse_position(&args, false, Variant::Standard).unwrap();
+        assert_eq!(board.get_fen(), "1N1rk3/8/8/8/8/8/8/5RK1 b - - 0 2");
+    }
+    #[test]
+    fn position_chess960_castling() {
+        let args: Vec<&str> = "fen 4k3/8/8/8/8/8/8/4K2R w K - 0 1 moves e1h1"
+            .split_whitespace()
+            .collect();
+        let board = parse_position(&args, true, Variant::Standard).unwrap();
+        assert_eq!(board.get_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1").unwrap();
+        let action = board.get_action_from_uci("e1g1").unwrap();
+        assert_eq!(board.get_uci_from_action(action), "e1g1");
+        let mut board = board;
+        board.chess960 = true;
+        assert_eq!(board.get_uci_from_action(action), "e1h1");
+    }
+    #[test]
+    fn position_in_a_variant() {
+        let args = ["startpos", "moves", "e2e4", "d7d5", "g1f3"];
+        assert!(parse_position(&args, false, Variant::Standard).is_ok());
+        assert!(matches!(
+            parse_position(&args, false, Variant::Antichess),
+            Err(UciError::InvalidMove(_))
+        ));
+        let mut uci = Uci::new();
+        uci.handle_command("setoption name UCI_Variant value 3check");
+        uci.handle_command("position startpos moves e2e4 d7d5 f1b5");
+        assert_eq!(uci.board.checks, [1, 0]);
+
+        // Switching back drops the antichess rules and the old entries.
+        uci.handle_command("setoption name UCI_Variant value antichess");
+        assert!(uci.board.rules.promotion_kinds.contains(&PieceKind::King));
+        let hash = uci.board.hash();
+        uci.get_tt().store(hash, 1, Bound::Exact, 0, None);
+        uci.handle_command("setoption name UCI_Variant value chess");
+        assert!(!uci.board.rules.promotion_kinds.contains(&PieceKind::King));
+        assert!(uci.get_tt().probe(hash).is_none());
+    }
+    #[test]
+    fn position_rejects_illegal_moves() {
+        assert!(matches!(
+            parse_position(&["startpos", "moves", "e2e5"], false, Variant::Standard),
+            Err(UciError::InvalidMove(_))
+        ));
+    }
+    #[test]
+    fn setoption_name_and_value() {
+        let args = ["name", "Hash", "value", "64"];
+        assert_eq!(
+            parse_setoption(&args),
+            Some((String::from("Hash"), String::from("64")))
+        );
+        assert_eq!(parse_setoption(&["value", "64"]), None);
+    }
+    #[test]
+    fn setoption_weights_evaluation_terms() {
+        let mut uci = Uci::new();
+        uci.handle_command("setoption name KingAttack value 0");
+        uci.handle_command("setoption name Mobility value 150");
+        uci.handle_command("setoption name PawnShield value 1000");
+        assert_eq!(uci.weights.king_attack, 0);
+        assert_eq!(uci.weights.mobility, 150);
+        assert_eq!(uci.weights.pawn_shield, 100);
+    }
+    #[test]
+    fn go_options() {
+        let options = parse_go(&[
+            "wtime",
+            "60000",
+            "btime",
+            "50000",
+            "winc",
+            "1000",
+            "movestogo",
+            "20",
+        ]);
+        assert_eq!(options.white_time, Some(60000));
+        assert_eq!(options.black_time, Some(50000));
+        assert_eq!(options.white_increment, Some(1000));
+        assert_eq!(options.moves_to_go, Some(20));
+        assert!(!options.infinite);
+        assert_eq!(parse_go(&["depth", "3"]).depth, Some(3));
+    }
+}
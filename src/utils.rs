@@ -1,3 +1,9 @@
+use crate::bitboard::*;
+use crate::crazyhouse::*;
+use crate::eval::*;
+use crate::score::*;
+use crate::variant::*;
+use crate::zobrist::*;
 use rand::seq::SliceRandom;
 use ActionKind::*;
 use CastlingKind::*;
@@ -17,6 +23,17 @@ pub enum PieceKind {
     Bishop,
     Queen,
     King,
+    /// Bishop and knight.
+    Archbishop,
+    /// Rook and knight.
+    Chancellor,
+    /// Queen and knight.
+    Amazon,
+    /// Leaps three squares one way and one the other.
+    Camel,
+    /// Moves along queen lines by hopping over the first piece in its way
+    /// onto the square right behind it.
+    Grasshopper,
 }
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PieceColor {
@@ -30,12 +47,15 @@ pub enum ActionKind {
     EnPassant,
     Castling(CastlingKind),
     Promotion(PieceKind),
+    /// Places a piece from the hand on the empty square `end`, which
+    /// `start` repeats.
+    Drop(PieceKind),
 }
 #[derive(Debug)]
 pub struct TreeNode {
     pub board: Board,
     pub action: Option<Action>,
-    pub value: f64,
+    pub value: Score,
     pub children: Vec<TreeNode>,
 }
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -43,7 +63,7 @@ pub struct Location {
     pub row: usize,
     pub col: usize,
 }
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Action {
     pub start: Location,
     pub end: Location,
@@ -54,17 +74,102 @@ pub struct Piece {
     pub kind: PieceKind,
     pub color: PieceColor,
     pub moved: bool,
+    /// Whether the piece was a pawn once, so that it goes back to the hand
+    /// as one when captured in crazyhouse.
+    pub promoted: bool,
 }
 type Position = Vec<Vec<Option<Piece>>>;
 
+/// The columns of the rooks each color may still castle with, indexed by
+/// `color_index`. In Chess960 they need not be the corners.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CastlingRights {
+    pub short: [Option<usize>; 2],
+    pub long: [Option<usize>; 2],
+}
+
+/// The rules that change with the size of the board or its setup.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rules {
+    /// Whether pawns may advance two squares from their starting row.
+    pub double_step: bool,
+    /// The pieces a pawn may promote to, most valuable first.
+    pub promotion_kinds: &'static [PieceKind],
+}
+
+impl Rules {
+    /// The usual rules for a board of `size`. Pawns only get a double step
+    /// once there is room for it, as on the small boards they would run
+    /// straight into each other. The 6x6 board plays Los Alamos chess, so
+    /// a board loaded from FEN keeps its rules.
+    pub fn new(size: usize) -> Rules {
+        Rules {
+            double_step: size >= 8,
+            promotion_kinds: if size == 6 {
+                &LOS_ALAMOS_PROMOTION_KINDS
+            } else {
+                &PROMOTION_KINDS
+            },
+        }
+    }
+}
+
+/// Everything `Board::unmake_move` needs to take back the move on top of
+/// the undo stack.
+#[derive(Debug, Clone, Copy)]
+pub struct Undo {
+    pub action: Action,
+    pub piece: Piece,
+    pub captured: Option<(Piece, Location)>,
+    pub castled_rook: Option<(Piece, Location)>,
+    pub castling: CastlingRights,
+    pub en_passant: Option<Location>,
+    pub halfmove_clock: usize,
+    pub fullmove_number: usize,
+    pub last_action: Option<Action>,
+    pub hash: u64,
+    /// The pieces blown up by an atomic capture, capturer included.
+    pub exploded: [Option<(Piece, Location)>; 9],
+    pub checks: [u8; 2],
+}
+
 #[derive(Debug, Clone)]
 pub struct Board {
     pub size: usize,
     pub position: Position,
     pub turn: PieceColor,
+    pub castling: CastlingRights,
+    pub en_passant: Option<Location>,
+    pub halfmove_clock: usize,
+    pub fullmove_number: usize,
     pub selected: Option<Location>,
     pub last_action: Option<Action>,
     pub action_list: Vec<Action>,
+    pub undo_stack: Vec<Undo>,
+    pub position_history: Vec<u64>,
+    /// Whether castling follows the Chess960 conventions in FEN and UCI.
+    pub chess960: bool,
+    pub rules: Rules,
+    /// The rules to go back to when leaving a variant that changes them.
+    pub saved_rules: Rules,
+    pub variant: Variant,
+    /// How many checks each side has given, which three-check counts.
+    pub checks: [u8; 2],
+    /// The pieces each side holds in crazyhouse, counted by kind.
+    pub hands: [[u8; PIECE_KINDS]; 2],
+    pub(crate) hash: u64,
+    pawn_hash: u64,
+    bitboards: Bitboards,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawKind {
+    Stalemate,
+    InsufficientMaterial,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
 }
 
 #[derive(Debug)]
@@ -75,8 +180,35 @@ pub enum MoveError {
     StartSquareEmpty,
     InvalidPieceColor,
     RemainsInCheck,
+    /// Antichess only allows captures while there is one.
+    CaptureRequired,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum FenError {
+    MissingPlacement,
+    /// More rows than the hash keys cover, `MAX_SIZE`.
+    InvalidSize,
+    TooManyFields,
+    InvalidPiece(char),
+    InvalidRowCount,
+    InvalidRowLength,
+    InvalidTurn,
+    InvalidCastling,
+    InvalidEnPassant,
+    InvalidChecks,
+    InvalidHand,
+    InvalidHalfmoveClock,
+    InvalidFullmoveNumber,
+}
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// The pieces a pawn may promote to, most valuable first.
+pub const PROMOTION_KINDS: [PieceKind; 4] = [Queen, Rook, Bishop, Knight];
+/// Los Alamos chess has no bishops to promote to.
+pub const LOS_ALAMOS_PROMOTION_KINDS: [PieceKind; 3] = [Queen, Rook, Knight];
+
 pub fn get_piece_kind_worth(kind: PieceKind) -> f64 {
     match kind {
         Pawn => 1.,
@@ -85,6 +217,67 @@ pub fn get_piece_kind_worth(kind: PieceKind) -> f64 {
         Bishop => 3.55,
         Queen => 10.,
         King => 4.,
+        Archbishop => 8.75,
+        Chancellor => 9.25,
+        Amazon => 13.5,
+        Camel => 2.5,
+        Grasshopper => 2.,
+    }
+}
+pub fn get_piece_kind_letter(kind: PieceKind) -> char {
+    match kind {
+        Pawn => 'p',
+        Rook => 'r',
+        Knight => 'n',
+        Bishop => 'b',
+        Queen => 'q',
+        King => 'k',
+        Archbishop => 'a',
+        Chancellor => 'c',
+        Amazon => 'z',
+        Camel => 'l',
+        Grasshopper => 'g',
+    }
+}
+pub fn get_piece_kind_from_letter(letter: char) -> Option<PieceKind> {
+    match letter.to_ascii_lowercase() {
+        'p' => Some(Pawn),
+        'r' => Some(Rook),
+        'n' => Some(Knight),
+        'b' => Some(Bishop),
+        'q' => Some(Queen),
+        'k' => Some(King),
+        'a' => Some(Archbishop),
+        'c' => Some(Chancellor),
+        'z' => Some(Amazon),
+        'l' => Some(Camel),
+        'g' => Some(Grasshopper),
+        _ => None,
+    }
+}
+/// The row a color's pieces start on.
+pub fn get_home_row(size: usize, color: PieceColor) -> usize {
+    if color == First {
+        size - 1
+    } else {
+        0
+    }
+}
+/// The columns the king and the rook end up on after castling.
+pub fn get_castled_cols(size: usize, kind: CastlingKind) -> (usize, usize) {
+    match kind {
+        Short => (size - 2, size - 3),
+        Long => (2, 3),
+    }
+}
+fn is_piece(piece: Option<Piece>, kind: PieceKind, color: PieceColor) -> bool {
+    piece.is_some_and(|piece| piece.kind == kind && piece.color == color)
+}
+pub fn color_index(color: PieceColor) -> usize {
+    if color == First {
+        0
+    } else {
+        1
     }
 }
 pub fn opposite_color(color: PieceColor) -> PieceColor {
@@ -106,77 +299,61 @@ pub fn sign_of_i8(num: i8) -> i8 {
 pub fn actions_to_ends(actions: Vec<Action>) -> Vec<Location> {
     actions.iter().map(|action| action.end).collect()
 }
-pub fn actions_to_algebraic_ends(actions: Vec<Action>) -> Vec<String> {
+pub fn actions_to_algebraic_ends(actions: Vec<Action>, size: usize) -> Vec<String> {
     actions_to_ends(actions)
         .iter()
-        .map(|end| location_to_algebraic(*end))
+        .map(|end| location_to_algebraic(*end, size))
         .collect()
 }
-pub fn algebraic_to_action(input: String) -> Action {
-    let input = input.trim();
-    if input.len() != 4 {
-        panic!("Invalid move length");
-    }
-    let mut start = Location { row: 0, col: 0 };
-    let mut end = Location { row: 0, col: 0 };
-    let kind = ActionKind::Normal;
-
-    let letters = "abcdefgh";
-
-    for (i, ch) in input.chars().enumerate() {
-        let lower = ch.to_ascii_lowercase();
-        if i % 2 == 0 && letters.contains(lower) {
-            let col = letters.find(lower).unwrap();
-            if i == 0 {
-                start.col = col;
-            } else if i == 2 {
-                end.col = col;
-            }
-        } else if ch.is_digit(10) && ch >= '1' && ch <= '8' {
-            let row = ch.to_digit(10).unwrap() - 1;
-            if i == 1 {
-                start.row = row as usize;
-            } else if i == 3 {
-                end.row = row as usize;
-            }
-        };
-    }
-
-    Action { start, end, kind }
+/// Reads the `3+3` field of a three-check FEN, the checks each side still
+/// has to give, into the checks given so far.
+fn parse_remaining_checks(field: &str) -> Result<[u8; 2], FenError> {
+    let (first, second) = field.split_once('+').ok_or(FenError::InvalidChecks)?;
+    let parse = |remaining: &str| match remaining.parse::<u8>() {
+        Ok(remaining) if remaining <= 3 => Ok(3 - remaining),
+        _ => Err(FenError::InvalidChecks),
+    };
+    Ok([parse(first)?, parse(second)?])
 }
-pub fn algebraic_to_location(input: String) -> Result<Location, MoveError> {
+/// Reads a square such as `e4` or, on larger boards, `j10`.
+pub fn algebraic_to_location(input: &str, size: usize) -> Result<Location, MoveError> {
     let input = input.trim();
-    if input.len() != 2 {
+    if !input.is_ascii() || input.len() < 2 || input.len() > 3 {
         return Err(MoveError::InvalidLocationStringLength);
     }
-    let mut location = Location { row: 0, col: 0 };
-
-    let letters = "abcdefgh";
-
-    for (i, ch) in input.chars().enumerate() {
-        let lower = ch.to_ascii_lowercase();
-        if i == 0 && letters.contains(lower) {
-            let col = letters.find(lower).unwrap();
-            location.col = col;
-        } else if i == 1 && ch.is_digit(10) && ch >= '1' && ch <= '8' {
-            let row = ch.to_digit(10).unwrap() - 1;
-            location.row = row as usize;
-        } else {
-            return Err(MoveError::InvalidLocationString);
-        }
+    let file = input.as_bytes()[0].to_ascii_lowercase();
+    if !file.is_ascii_lowercase() || !input[1..].bytes().all(|c| c.is_ascii_digit()) {
+        return Err(MoveError::InvalidLocationString);
+    }
+    let col = (file - b'a') as usize;
+    let rank: usize = input[1..]
+        .parse()
+        .map_err(|_| MoveError::InvalidLocationString)?;
+    if col >= size || rank == 0 || rank > size {
+        return Err(MoveError::InvalidLocationString);
     }
-    Ok(location)
+    Ok(Location {
+        row: size - rank,
+        col,
+    })
 }
-pub fn location_to_algebraic(location: Location) -> String {
+pub fn location_to_algebraic(location: Location, size: usize) -> String {
     let Location { row, col } = location;
-    let mut string = String::new();
-
-    let rank = (row as u8 + b'1') as char;
     let file = (col as u8 + b'a') as char;
-
-    string.push(file);
-    string.push(rank);
-
+    format!("{}{}", file, size - row)
+}
+/// Formats an action in the long algebraic notation used by UCI, such as
+/// `e2e4` or `e7e8q`.
+pub fn action_to_uci(action: Action, size: usize) -> String {
+    if let Drop(kind) = action.kind {
+        let letter = get_piece_kind_letter(kind).to_ascii_uppercase();
+        return format!("{}@{}", letter, location_to_algebraic(action.end, size));
+    }
+    let mut string = location_to_algebraic(action.start, size);
+    string.push_str(&location_to_algebraic(action.end, size));
+    if let Promotion(kind) = action.kind {
+        string.push(get_piece_kind_letter(kind));
+    }
     string
 }
 
@@ -197,35 +374,39 @@ pub fn count_tree_nodes(node: &TreeNode) -> usize {
     }
     sum
 }
-pub fn update_tree(node: &mut TreeNode) {
+pub fn update_tree<E: Evaluator + ?Sized>(node: &mut TreeNode, evaluator: &mut E) {
+    update_subtree(node, evaluator, 0);
+}
+/// Minimax over the tree below `node`, which is `ply` plies from the root,
+/// so that shorter mates are worth more.
+fn update_subtree<E: Evaluator + ?Sized>(node: &mut TreeNode, evaluator: &mut E, ply: usize) {
     let func = if node.board.turn == First {
-        f64::max
+        Score::max
     } else {
-        f64::min
+        Score::min
     };
     let mut best = if node.board.turn == First {
-        -f64::INFINITY
-    } else {
-        f64::INFINITY
-    };
-    let checkmate_worth = if node.board.turn == First {
-        -f64::INFINITY
+        -INFINITY
     } else {
-        f64::INFINITY
+        INFINITY
     };
     for child in node.children.iter_mut() {
-        update_tree(child);
+        update_subtree(child, evaluator, ply + 1);
         best = func(best, child.value);
     }
-    if node.children.is_empty() {
-        if node.board.is_checkmate() {
-            node.value = checkmate_worth;
-        } else {
-            node.value = node.board.get_material_difference();
-        }
-    } else {
+    if !node.children.is_empty() {
         node.value = best;
+        return;
     }
+    // The end values are from the side to move's point of view.
+    let sign = if node.board.turn == First { 1 } else { -1 };
+    node.value = if let Some(value) = get_variant_value(&node.board, ply) {
+        sign * value
+    } else if node.board.is_moveless() {
+        sign * get_moveless_value(&node.board, ply)
+    } else {
+        evaluator.evaluate(&node.board)
+    };
 }
 
 fn is_valid_promotion(board: &Board, start: Location, end: Location, color: PieceColor) -> bool {
@@ -235,40 +416,27 @@ fn is_valid_promotion(board: &Board, start: Location, end: Location, color: Piec
     if is_valid_pawn_capture(board, size, start, end, color) {
         return true;
     }
-    if board.get_piece_from_location(end) == None
-        && is_valid_pawn_translation(board.size, start.row, dx, dy, color)
+    if board.get_piece_from_location(end).is_none()
+        && is_valid_pawn_translation(board, start.row, dx, dy, color)
     {
         return true;
     }
     false
 }
-fn is_valid_en_passant(
-    board: &Board,
-    size: usize,
-    start: Location,
-    end: Location,
-    color: PieceColor,
-) -> bool {
+fn is_valid_en_passant(board: &Board, start: Location, end: Location, color: PieceColor) -> bool {
     let dx = end.col as i8 - start.col as i8;
     let dy = end.row as i8 - start.row as i8;
 
     let direction: i8 = if color == First { -1 } else { 1 };
-    // let home_row = if direction == 1 { 1 } else { size - 2 };
-    let opposite_home_row = if direction == 1 { size - 2 } else { 1 };
 
-    if dx.abs() == 1 && dy == direction {
-        let end = Location {
-            row: (end.row as i8 - direction) as usize,
+    if dx.abs() == 1 && dy == direction && board.en_passant == Some(end) {
+        let captured = Location {
+            row: start.row,
             col: end.col,
         };
-        if start.row as i8 != opposite_home_row as i8 - 2 * direction {
-            return false;
-        }
-        if let Some(p) = board.get_piece_from_location(end) {
-            if let Some(action) = board.last_action {
-                if p.color != color && action.end.row == start.row && action.end.col == end.col {
-                    return true;
-                }
+        if let Some(p) = board.get_piece_from_location(captured) {
+            if p.kind == Pawn && p.color != color {
+                return true;
             }
         }
     }
@@ -300,20 +468,20 @@ fn is_valid_pawn_capture(
     false
 }
 fn is_valid_pawn_translation(
-    size: usize,
+    board: &Board,
     start_row: usize,
     dx: i8,
     dy: i8,
     color: PieceColor,
 ) -> bool {
     let direction: i8 = if color == First { -1 } else { 1 };
-    let home_row = if direction == 1 { 1 } else { size - 2 };
+    let home_row = if direction == 1 { 1 } else { board.size - 2 };
 
     if dx == 0 {
         if dy == direction {
             return true;
         };
-        if start_row == home_row && dy == 2 * direction {
+        if board.rules.double_step && start_row == home_row && dy == 2 * direction {
             return true;
         };
     }
@@ -355,6 +523,22 @@ fn is_valid_king_translation(dx: i8, dy: i8) -> bool {
 
     false
 }
+fn is_valid_archbishop_translation(dx: i8, dy: i8) -> bool {
+    is_valid_bishop_translation(dx, dy) || is_valid_knight_translation(dx, dy)
+}
+fn is_valid_chancellor_translation(dx: i8, dy: i8) -> bool {
+    is_valid_rook_translation(dx, dy) || is_valid_knight_translation(dx, dy)
+}
+fn is_valid_amazon_translation(dx: i8, dy: i8) -> bool {
+    is_valid_queen_translation(dx, dy) || is_valid_knight_translation(dx, dy)
+}
+fn is_valid_camel_translation(dx: i8, dy: i8) -> bool {
+    matches!((dx.abs(), dy.abs()), (1, 3) | (3, 1))
+}
+/// The hurdle itself is checked by `Board::is_path_open`.
+fn is_valid_grasshopper_translation(dx: i8, dy: i8) -> bool {
+    is_valid_queen_translation(dx, dy) && dx.abs().max(dy.abs()) >= 2
+}
 
 impl Piece {
     pub fn new(kind: PieceKind, color: PieceColor) -> Piece {
@@ -362,6 +546,7 @@ impl Piece {
             kind,
             color,
             moved: false,
+            promoted: false,
         }
     }
 }
@@ -372,62 +557,458 @@ impl Board {
             size,
             position: vec![vec![None; size]; size],
             turn: First,
+            castling: CastlingRights::default(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
             selected: None,
             last_action: None,
             action_list: Vec::new(),
+            undo_stack: Vec::new(),
+            position_history: Vec::new(),
+            chess960: false,
+            rules: Rules::new(size),
+            saved_rules: Rules::new(size),
+            variant: Variant::Standard,
+            checks: [0; 2],
+            hands: [[0; PIECE_KINDS]; 2],
+            hash: 0,
+            pawn_hash: 0,
+            bitboards: Bitboards::default(),
         }
     }
     pub fn clear(&mut self) {
         self.position = vec![vec![None; self.size]; self.size];
+        self.hash = self.compute_hash();
+        self.pawn_hash = 0;
+        self.bitboards = Bitboards::default();
     }
     pub fn set_piece(&mut self, piece: Piece, location: Location) {
+        self.clear_piece(location);
         let Location { row, col } = location;
         self.position[row][col] = Some(piece);
+        self.hash ^= get_keys().piece(piece, location);
+        if piece.kind == Pawn {
+            self.pawn_hash ^= get_keys().piece(piece, location);
+        }
+        if self.size <= WIDTH {
+            self.bitboards.set(piece, location);
+        }
     }
     pub fn clear_piece(&mut self, location: Location) {
         let Location { row, col } = location;
-        self.position[row][col] = None;
-    }
-    pub fn set_fen(&mut self, fen: &String) {
-        self.clear();
-        let mut row: usize = 0;
-        let mut col: usize = 0;
-        for char in fen.chars() {
-            match char {
-                '/' => {
-                    row += 1;
-                    col = 0;
+        if let Some(piece) = self.position[row][col].take() {
+            self.hash ^= get_keys().piece(piece, location);
+            if piece.kind == Pawn {
+                self.pawn_hash ^= get_keys().piece(piece, location);
+            }
+            if self.size <= WIDTH {
+                self.bitboards.clear(piece, location);
+            }
+        }
+    }
+    /// The piece placement as bitboards, which only boards of up to 8x8
+    /// squares keep.
+    pub fn get_bitboards(&self) -> Option<&Bitboards> {
+        if self.size <= WIDTH {
+            Some(&self.bitboards)
+        } else {
+            None
+        }
+    }
+    fn compute_bitboards(&self) -> Bitboards {
+        let mut bitboards = Bitboards::default();
+        if self.size > WIDTH {
+            return bitboards;
+        }
+        for row in 0..self.size {
+            for col in 0..self.size {
+                if let Some(piece) = self.position[row][col] {
+                    bitboards.set(piece, Location { row, col });
                 }
-                '1'..='9' => {
-                    col += char.to_digit(10).unwrap() as usize;
+            }
+        }
+        bitboards
+    }
+    /// The Zobrist hash of the position: piece placement, side to move,
+    /// castling rights and a capturable en passant file.
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+    /// Hashes the position from scratch. `make_move` keeps `hash()` equal to
+    /// this without rescanning the board.
+    pub fn compute_hash(&self) -> u64 {
+        let keys = get_keys();
+        let mut hash = self.get_state_hash();
+        if self.turn == Second {
+            hash ^= keys.turn;
+        }
+        for row in 0..self.size {
+            for col in 0..self.size {
+                if let Some(piece) = self.position[row][col] {
+                    hash ^= keys.piece(piece, Location { row, col });
                 }
-                letter => {
-                    let color = if char.is_lowercase() { Second } else { First };
-                    let kind = match char.to_lowercase().next().unwrap() {
-                        'p' => Pawn,
-                        'r' => Rook,
-                        'n' => Knight,
-                        'b' => Bishop,
-                        'q' => Queen,
-                        'k' => King,
-                        _ => {
-                            panic!("Invalid FEN!");
+            }
+        }
+        for color in [First, Second] {
+            for kind in HAND_KINDS {
+                for count in 1..=self.get_hand_count(color, kind) {
+                    hash ^= keys.hand(color, kind, count);
+                }
+            }
+        }
+        hash
+    }
+    /// The hash of the pawns alone, which the pawn structure evaluation is
+    /// cached under.
+    pub fn pawn_hash(&self) -> u64 {
+        self.pawn_hash
+    }
+    /// Hashes the pawns from scratch, like `compute_hash` does the whole
+    /// position.
+    pub fn compute_pawn_hash(&self) -> u64 {
+        let keys = get_keys();
+        let mut hash = 0;
+        for row in 0..self.size {
+            for col in 0..self.size {
+                if let Some(piece) = self.position[row][col] {
+                    if piece.kind == Pawn {
+                        hash ^= keys.piece(piece, Location { row, col });
+                    }
+                }
+            }
+        }
+        hash
+    }
+    /// The part of the hash that covers castling rights, en passant and the
+    /// checks given in three-check.
+    fn get_state_hash(&self) -> u64 {
+        let keys = get_keys();
+        let mut hash = 0;
+        for index in 0..2 {
+            if self.castling.short[index].is_some() {
+                hash ^= keys.castling[index][0];
+            }
+            if self.castling.long[index].is_some() {
+                hash ^= keys.castling[index][1];
+            }
+        }
+        if let Some(location) = self.en_passant {
+            if self.is_en_passant_capturable() {
+                hash ^= keys.en_passant(location.col);
+            }
+        }
+        for index in 0..2 {
+            if self.checks[index] > 0 {
+                hash ^= keys.checks(index, self.checks[index]);
+            }
+        }
+        hash
+    }
+    pub fn from_fen(fen: &str) -> Result<Board, FenError> {
+        let placement = fen.split_whitespace().next();
+        let size = match placement {
+            Some(placement) => placement.split('/').count(),
+            None => return Err(FenError::MissingPlacement),
+        };
+        if size > MAX_SIZE {
+            return Err(FenError::InvalidSize);
+        }
+        let mut board = Board::new(size);
+        board.set_fen(fen)?;
+        Ok(board)
+    }
+    pub fn set_fen(&mut self, fen: &str) -> Result<(), FenError> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.is_empty() {
+            return Err(FenError::MissingPlacement);
+        }
+        // Three-check positions carry the checks each side has left, as in
+        // `3+3`, right after the en passant square.
+        let checks = match fields.get(4) {
+            Some(field) if field.contains('+') => Some(parse_remaining_checks(fields.remove(4))?),
+            _ => None,
+        };
+        if fields.len() > 6 {
+            return Err(FenError::TooManyFields);
+        }
+
+        // Crazyhouse hands follow the placement in brackets, as in `[Qp]`.
+        let (placement, hands) = match fields[0].split_once('[') {
+            Some((placement, hand)) => {
+                let hand = hand.strip_suffix(']').ok_or(FenError::InvalidHand)?;
+                (placement, Some(parse_hands(hand)?))
+            }
+            None => (fields[0], None),
+        };
+        let position = self.parse_fen_placement(placement)?;
+
+        let turn = match fields.get(1).copied().unwrap_or("w") {
+            "w" => First,
+            "b" => Second,
+            _ => return Err(FenError::InvalidTurn),
+        };
+
+        let mut castling = CastlingRights::default();
+        let mut chess960 = false;
+        let castling_field = fields.get(2).copied().unwrap_or("-");
+        if castling_field != "-" {
+            for char in castling_field.chars() {
+                let color = if char.is_lowercase() { Second } else { First };
+                let index = color_index(color);
+                let home_row = get_home_row(self.size, color);
+                let king_col = (0..self.size)
+                    .find(|&col| is_piece(position[home_row][col], King, color))
+                    .ok_or(FenError::InvalidCastling)?;
+                let is_rook = |col: &usize| is_piece(position[home_row][*col], Rook, color);
+                // X-FEN: K and Q stand for the outermost rook on their side,
+                // a file letter for any other one, as in Shredder-FEN.
+                match char.to_ascii_lowercase() {
+                    'k' => {
+                        let col = (king_col + 1..self.size).rev().find(is_rook);
+                        castling.short[index] = Some(col.ok_or(FenError::InvalidCastling)?);
+                    }
+                    'q' => {
+                        let col = (0..king_col).find(is_rook);
+                        castling.long[index] = Some(col.ok_or(FenError::InvalidCastling)?);
+                    }
+                    file @ 'a'..='z' => {
+                        let col = (file as u8 - b'a') as usize;
+                        if col >= self.size || !is_rook(&col) {
+                            return Err(FenError::InvalidCastling);
+                        }
+                        if col > king_col {
+                            castling.short[index] = Some(col);
+                        } else {
+                            castling.long[index] = Some(col);
                         }
+                        chess960 = true;
+                    }
+                    _ => return Err(FenError::InvalidCastling),
+                }
+            }
+        }
+
+        let en_passant = match fields.get(3).copied().unwrap_or("-") {
+            "-" => None,
+            square => match algebraic_to_location(square, self.size) {
+                Ok(location) => Some(location),
+                Err(_) => return Err(FenError::InvalidEnPassant),
+            },
+        };
+
+        let halfmove_clock = match fields.get(4) {
+            Some(field) => field.parse().map_err(|_| FenError::InvalidHalfmoveClock)?,
+            None => 0,
+        };
+        let fullmove_number = match fields.get(5) {
+            Some(field) => match field.parse() {
+                Ok(number) if number > 0 => number,
+                _ => return Err(FenError::InvalidFullmoveNumber),
+            },
+            None => 1,
+        };
+
+        self.position = position;
+        self.bitboards = self.compute_bitboards();
+        self.turn = turn;
+        self.castling = castling;
+        self.chess960 |= chess960;
+        self.en_passant = en_passant;
+        self.halfmove_clock = halfmove_clock;
+        self.fullmove_number = fullmove_number;
+        if checks.is_some() {
+            self.variant = Variant::ThreeCheck;
+        }
+        self.checks = checks.unwrap_or_default();
+        if hands.is_some() {
+            self.variant = Variant::Crazyhouse;
+        }
+        self.hands = hands.unwrap_or_default();
+        self.selected = None;
+        self.last_action = None;
+        self.action_list = Vec::new();
+        self.undo_stack = Vec::new();
+        self.update_moved_flags();
+        self.hash = self.compute_hash();
+        self.pawn_hash = self.compute_pawn_hash();
+        self.position_history = vec![self.hash];
+        Ok(())
+    }
+    fn parse_fen_placement(&self, placement: &str) -> Result<Position, FenError> {
+        let rows: Vec<&str> = placement.split('/').collect();
+        if rows.len() != self.size {
+            return Err(FenError::InvalidRowCount);
+        }
+        let mut position = vec![vec![None; self.size]; self.size];
+        for (row, fen_row) in rows.iter().enumerate() {
+            let mut col: usize = 0;
+            let mut empty: usize = 0;
+            for char in fen_row.chars() {
+                if char == '~' {
+                    // Marks the piece before it as promoted.
+                    let piece: Option<&mut Piece> = match col.checked_sub(1) {
+                        Some(col) if empty == 0 => position[row][col].as_mut(),
+                        _ => None,
                     };
-                    let piece = Piece::new(kind, color);
-                    self.set_piece(piece, Location { row, col });
-                    col += 1;
+                    piece.ok_or(FenError::InvalidPiece(char))?.promoted = true;
+                    continue;
                 }
-            };
+                if let Some(digit) = char.to_digit(10) {
+                    empty = empty * 10 + digit as usize;
+                    continue;
+                }
+                col += empty;
+                empty = 0;
+                let kind = get_piece_kind_from_letter(char).ok_or(FenError::InvalidPiece(char))?;
+                let color = if char.is_lowercase() { Second } else { First };
+                if col >= self.size {
+                    return Err(FenError::InvalidRowLength);
+                }
+                position[row][col] = Some(Piece::new(kind, color));
+                col += 1;
+            }
+            col += empty;
+            if col != self.size {
+                return Err(FenError::InvalidRowLength);
+            }
         }
+        Ok(position)
     }
+    /// Derives the `moved` flags of kings and rooks from the castling rights,
+    /// so that positions loaded from FEN castle exactly as the rights say.
+    fn update_moved_flags(&mut self) {
+        for color in [First, Second] {
+            let index = color_index(color);
+            let home_row = get_home_row(self.size, color);
+            let short = self.castling.short[index];
+            let long = self.castling.long[index];
+            for col in 0..self.size {
+                let location = Location { row: home_row, col };
+                if let Some(mut piece) = self.get_piece_from_location(location) {
+                    if piece.color != color {
+                        continue;
+                    }
+                    piece.moved = match piece.kind {
+                        King => short.is_none() && long.is_none(),
+                        Rook => short != Some(col) && long != Some(col),
+                        _ => continue,
+                    };
+                    self.set_piece(piece, location);
+                }
+            }
+        }
+    }
+    pub fn get_fen(&self) -> String {
+        let mut fen = String::new();
+        for row in 0..self.size {
+            let mut empty = 0;
+            for col in 0..self.size {
+                match self.position[row][col] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        let letter = get_piece_kind_letter(piece.kind);
+                        if piece.color == First {
+                            fen.push(letter.to_ascii_uppercase());
+                        } else {
+                            fen.push(letter);
+                        }
+                        if piece.promoted && self.variant == Variant::Crazyhouse {
+                            fen.push('~');
+                        }
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if row + 1 < self.size {
+                fen.push('/');
+            }
+        }
+        if self.variant == Variant::Crazyhouse {
+            fen.push_str(&format!("[{}]", self.get_hand_fen()));
+        }
+
+        fen.push_str(if self.turn == First { " w " } else { " b " });
 
+        let mut castling = String::new();
+        for color in [First, Second] {
+            for kind in [Short, Long] {
+                if let Some(letter) = self.get_castling_letter(color, kind) {
+                    castling.push(letter);
+                }
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+        fen.push_str(&castling);
+
+        fen.push(' ');
+        match self.en_passant {
+            Some(location) => fen.push_str(&location_to_algebraic(location, self.size)),
+            None => fen.push('-'),
+        }
+        if self.variant == Variant::ThreeCheck {
+            let [first, second] = self.checks.map(|checks| 3u8.saturating_sub(checks));
+            fen.push_str(&format!(" {}+{}", first, second));
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock, self.fullmove_number
+        ));
+        fen
+    }
+
+    /// The X-FEN letter of a castling right: `K` or `Q` when the rook is the
+    /// outermost one on its side, otherwise the file of the rook.
+    fn get_castling_letter(&self, color: PieceColor, kind: CastlingKind) -> Option<char> {
+        let index = color_index(color);
+        let col = match kind {
+            Short => self.castling.short[index]?,
+            Long => self.castling.long[index]?,
+        };
+        let home_row = get_home_row(self.size, color);
+        let outer = match kind {
+            Short => col + 1..self.size,
+            Long => 0..col,
+        };
+        let is_outermost = outer
+            .into_iter()
+            .all(|col| !is_piece(self.position[home_row][col], Rook, color));
+        let letter = match (is_outermost, kind) {
+            (true, Short) => 'k',
+            (true, Long) => 'q',
+            (false, _) => (b'a' + col as u8) as char,
+        };
+        Some(if color == First {
+            letter.to_ascii_uppercase()
+        } else {
+            letter
+        })
+    }
     pub fn get_piece_from_location(&self, location: Location) -> Option<Piece> {
         let Location { row, col } = location;
         self.position[row][col]
     }
     pub fn get_location_from_piece(&self, piece: Piece) -> Option<Location> {
-        let Piece { kind, color, moved } = piece;
+        let Piece {
+            kind, color, moved, ..
+        } = piece;
+        if let Some(bitboards) = self.get_bitboards() {
+            // The scan below keeps the last match, which is the highest bit.
+            let pieces = bitboards.get_pieces(kind, color);
+            if pieces == 0 {
+                return None;
+            }
+            return Some(get_location(63 - pieces.leading_zeros() as usize));
+        }
         let mut location = Location { row: 0, col: 0 };
         let mut found_piece = false;
 
@@ -450,120 +1031,398 @@ impl Board {
     }
 
     pub fn commit_move(&mut self, action: Action) -> Result<(), MoveError> {
-        let test = self.test_move(action);
-        if let Err(message) = test {
-            return Err(message);
-        }
+        self.test_move(action)?;
         self.make_move(action);
-        if let Some(last_action) = self.last_action {
-            self.action_list.push(last_action);
-        }
-        self.last_action = Some(action);
-        self.turn = opposite_color(self.turn);
         self.selected = None;
         Ok(())
     }
+    /// Plays `action` without checking it and hands the turn over. Every call
+    /// pushes onto the undo stack, so it can be taken back with `unmake_move`.
+    ///
+    /// Panics if no piece stands on `action.start`, as nothing could be
+    /// pushed for it; use `commit_move` for actions that are not known to
+    /// be valid.
     pub fn make_move(&mut self, action: Action) {
         let Action { start, end, kind } = action;
-        let start_piece = self.get_piece_from_location(start);
+        let start_piece = match (kind, self.get_piece_from_location(start)) {
+            (Drop(dkind), _) => Piece::new(dkind, self.turn),
+            (_, Some(piece)) => piece,
+            (_, None) => panic!("No piece to move on {:?}", start),
+        };
+        // A castling king may end on its own square or its rook's.
+        let end_piece = match kind {
+            Castling(_) | Drop(_) => None,
+            _ => self.get_piece_from_location(end),
+        };
 
-        if let Some(mut start_piece) = start_piece {
-            start_piece.moved = true;
-            match kind {
-                Normal => {
-                    self.clear_piece(start);
-                    self.clear_piece(end);
-                    self.set_piece(start_piece, end);
-                }
-                Capture => {
-                    self.clear_piece(start);
-                    self.set_piece(start_piece, end);
+        let mut undo = Undo {
+            action,
+            piece: start_piece,
+            captured: end_piece.map(|piece| (piece, end)),
+            castled_rook: None,
+            castling: self.castling,
+            en_passant: self.en_passant,
+            halfmove_clock: self.halfmove_clock,
+            fullmove_number: self.fullmove_number,
+            last_action: self.last_action,
+            hash: self.hash,
+            exploded: [None; 9],
+            checks: self.checks,
+        };
+
+        self.hash ^= self.get_state_hash();
+
+        let castled_rook = match kind {
+            Castling(ckind) => self.get_castling_rook(self.turn, ckind),
+            _ => None,
+        };
+        self.update_castling_rights(start_piece, start, end);
+        self.en_passant = None;
+        if start_piece.kind == Pawn || end_piece.is_some() || kind == EnPassant {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if start_piece.color == Second {
+            self.fullmove_number += 1;
+        }
+
+        let mut start_piece = start_piece;
+        start_piece.moved = true;
+        match kind {
+            Normal => {
+                self.clear_piece(start);
+                self.clear_piece(end);
+                self.set_piece(start_piece, end);
+                if start_piece.kind == Pawn && start.row.abs_diff(end.row) == 2 {
+                    self.en_passant = Some(Location {
+                        row: (start.row + end.row) / 2,
+                        col: start.col,
+                    });
                 }
-                Castling(ckind) => {
-                    let direction: i8 = if self.turn == First { -1 } else { 1 };
-                    let home_row = if direction == 1 { 0 } else { self.size - 1 };
-                    let rook_col = if ckind == Long { 0 } else { self.size - 1 };
-
-                    let king_location = Location {
-                        row: home_row,
-                        col: 4,
-                    };
-                    let mut king = self.get_piece_from_location(king_location).unwrap();
-                    king.moved = true;
-                    let dir: i8 = if ckind == Long { -1 } else { 1 };
-                    let rook_location = Location {
-                        row: home_row,
-                        col: rook_col,
-                    };
+            }
+            Capture => {
+                self.clear_piece(start);
+                self.set_piece(start_piece, end);
+            }
+            Castling(ckind) => {
+                let (king_col, rook_col) = get_castled_cols(self.size, ckind);
+                // The king may land on its rook's square, so both leave the
+                // back row before either is put back.
+                self.clear_piece(start);
+                if let Some(rook_location) = castled_rook {
                     let mut rook = self.get_piece_from_location(rook_location).unwrap();
+                    undo.castled_rook = Some((rook, rook_location));
                     rook.moved = true;
-                    let new_king_location = Location {
-                        row: home_row,
-                        col: (4 + 2 * dir) as usize,
-                    };
+                    self.clear_piece(rook_location);
                     let new_rook_location = Location {
-                        row: home_row,
-                        col: (4 + dir) as usize,
+                        row: start.row,
+                        col: rook_col,
                     };
-                    self.clear_piece(king_location);
-                    self.clear_piece(rook_location);
-                    self.set_piece(king, new_king_location);
                     self.set_piece(rook, new_rook_location);
                 }
-                EnPassant => {
-                    self.clear_piece(start);
-                    self.clear_piece(Location {
-                        row: start.row,
-                        col: end.col,
-                    });
-                    self.set_piece(start_piece, end);
+                let new_king_location = Location {
+                    row: start.row,
+                    col: king_col,
+                };
+                self.set_piece(start_piece, new_king_location);
+            }
+            EnPassant => {
+                let captured_location = Location {
+                    row: start.row,
+                    col: end.col,
+                };
+                undo.captured = self
+                    .get_piece_from_location(captured_location)
+                    .map(|piece| (piece, captured_location));
+                self.clear_piece(start);
+                self.clear_piece(captured_location);
+                self.set_piece(start_piece, end);
+            }
+            Promotion(pkind) => {
+                self.clear_piece(start);
+                self.clear_piece(end);
+                let mut new_piece = Piece::new(pkind, self.turn);
+                new_piece.moved = true;
+                new_piece.promoted = true;
+                self.set_piece(new_piece, end);
+            }
+            Drop(dkind) => {
+                self.remove_from_hand(self.turn, dkind);
+                self.set_piece(start_piece, end);
+            }
+        };
+        if self.variant == Variant::Atomic && undo.captured.is_some() {
+            self.explode(end, &mut undo);
+        }
+        if let (Variant::Crazyhouse, Some((captured, _))) = (self.variant, undo.captured) {
+            self.add_to_hand(self.turn, get_hand_kind(captured));
+        }
+
+        self.undo_stack.push(undo);
+        self.action_list.push(action);
+        self.last_action = Some(action);
+        self.turn = opposite_color(self.turn);
+        if self.variant == Variant::ThreeCheck && self.is_check(self.turn) {
+            self.checks[color_index(start_piece.color)] += 1;
+        }
+        self.hash ^= get_keys().turn ^ self.get_state_hash();
+        self.position_history.push(self.hash);
+    }
+    /// Takes back the last move made with `make_move`, restoring the pieces,
+    /// their `moved` flags and the castling, en passant and clock state.
+    pub fn unmake_move(&mut self) -> Option<Action> {
+        let undo = self.undo_stack.pop()?;
+        let Action { start, end, kind } = undo.action;
+
+        self.turn = opposite_color(self.turn);
+        for &(piece, location) in undo.exploded.iter().flatten() {
+            self.set_piece(piece, location);
+        }
+        match kind {
+            Castling(ckind) => {
+                let (king_col, rook_col) = get_castled_cols(self.size, ckind);
+                self.clear_piece(Location {
+                    row: start.row,
+                    col: king_col,
+                });
+                self.clear_piece(Location {
+                    row: start.row,
+                    col: rook_col,
+                });
+                if let Some((rook, rook_location)) = undo.castled_rook {
+                    self.set_piece(rook, rook_location);
                 }
-                Promotion(pkind) => {
-                    self.clear_piece(start);
-                    self.clear_piece(end);
-                    let new_piece = Piece::new(pkind, self.turn);
-                    self.set_piece(new_piece, end);
+            }
+            _ => self.clear_piece(end),
+        }
+        match kind {
+            Drop(dkind) => self.add_to_hand(self.turn, dkind),
+            _ => self.set_piece(undo.piece, start),
+        }
+        if let Some((captured, captured_location)) = undo.captured {
+            if self.variant == Variant::Crazyhouse {
+                self.remove_from_hand(self.turn, get_hand_kind(captured));
+            }
+            self.set_piece(captured, captured_location);
+        }
+
+        self.castling = undo.castling;
+        self.en_passant = undo.en_passant;
+        self.halfmove_clock = undo.halfmove_clock;
+        self.fullmove_number = undo.fullmove_number;
+        self.checks = undo.checks;
+        self.last_action = undo.last_action;
+        self.hash = undo.hash;
+        self.action_list.pop();
+        self.position_history.pop();
+        Some(undo.action)
+    }
+    /// Drops the castling rights that a move of `piece` from `start` to `end`
+    /// loses: every right of a moving king, and the right of a rook that
+    /// leaves or is captured on its castling square.
+    pub(crate) fn update_castling_rights(&mut self, piece: Piece, start: Location, end: Location) {
+        if piece.kind == King {
+            let index = color_index(piece.color);
+            self.castling.short[index] = None;
+            self.castling.long[index] = None;
+        }
+        for color in [First, Second] {
+            let index = color_index(color);
+            let home_row = get_home_row(self.size, color);
+            for right in [
+                &mut self.castling.short[index],
+                &mut self.castling.long[index],
+            ] {
+                if let Some(col) = *right {
+                    let rook_location = Location { row: home_row, col };
+                    if start == rook_location || end == rook_location {
+                        *right = None;
+                    }
                 }
-            };
+            }
         }
     }
-    pub fn test_move(&mut self, action: Action) -> Result<(), MoveError> {
-        let Action { start, end, kind } = action;
-        let start_piece = self.get_piece_from_location(start);
-        if start_piece == None {
-            return Err(MoveError::StartSquareEmpty);
+    /// The rook `color` castles with on the `kind` side, if it may still.
+    pub fn get_castling_rook(&self, color: PieceColor, kind: CastlingKind) -> Option<Location> {
+        let index = color_index(color);
+        let col = match kind {
+            Short => self.castling.short[index]?,
+            Long => self.castling.long[index]?,
+        };
+        Some(Location {
+            row: get_home_row(self.size, color),
+            col,
+        })
+    }
+    /// The castling action of the side to move: the king goes to the c or g
+    /// file wherever it starts, as in Chess960.
+    pub fn get_castling_action(&self, kind: CastlingKind) -> Option<Action> {
+        let rook = self.get_castling_rook(self.turn, kind)?;
+        let home_row = rook.row;
+        let king_col =
+            (0..self.size).find(|&col| is_piece(self.position[home_row][col], King, self.turn))?;
+        let (end_col, _) = get_castled_cols(self.size, kind);
+        Some(Action {
+            start: Location {
+                row: home_row,
+                col: king_col,
+            },
+            end: Location {
+                row: home_row,
+                col: end_col,
+            },
+            kind: Castling(kind),
+        })
+    }
+    /// Whether the side to move may castle on the `kind` side now: the king
+    /// and rook are in place, every square either of them crosses is empty
+    /// apart from the two of them, and the king is never attacked on its way.
+    pub fn can_castle(&self, kind: CastlingKind) -> bool {
+        let action = match self.get_castling_action(kind) {
+            Some(action) => action,
+            None => return false,
+        };
+        let rook = self.get_castling_rook(self.turn, kind).unwrap();
+        if !is_piece(self.get_piece_from_location(rook), Rook, self.turn) {
+            return false;
         }
-        let start_piece = start_piece.unwrap();
-        // let end_piece = self.get_piece_from_location(end);
+        let king = action.start;
+        let home_row = king.row;
+        let (king_col, rook_col) = get_castled_cols(self.size, kind);
+        let span = |from: usize, to: usize| from.min(to)..=from.max(to);
+
+        for col in span(king.col, king_col).chain(span(rook.col, rook_col)) {
+            let location = Location { row: home_row, col };
+            if location != king
+                && location != rook
+                && self.get_piece_from_location(location).is_some()
+            {
+                return false;
+            }
+        }
+        let enemy = opposite_color(self.turn);
+        span(king.col, king_col).all(|col| {
+            let location = Location { row: home_row, col };
+            self.get_attackers(location, enemy, &[king, rook])
+                .is_empty()
+        })
+    }
+    pub fn test_move(&mut self, action: Action) -> Result<(), MoveError> {
+        let start_piece = match (action.kind, self.get_piece_from_location(action.start)) {
+            (Drop(kind), _) => Piece::new(kind, self.turn),
+            (_, Some(piece)) => piece,
+            (_, None) => return Err(MoveError::StartSquareEmpty),
+        };
 
         if self.turn != start_piece.color {
-            // println!("It's {:?}'s turn", self.turn);
             return Err(MoveError::InvalidPieceColor);
         }
 
         if !self.is_valid_action(action) {
-            // println!("Invalid action");
             return Err(MoveError::InvalidAction);
         };
 
-        let current_position = self.position.clone();
+        match self.variant {
+            Variant::Antichess => {
+                if !self.is_capture(action) && self.has_capture() {
+                    return Err(MoveError::CaptureRequired);
+                }
+                return Ok(());
+            }
+            // Explosions can take the king out of check or blow it up, so
+            // the move is played out.
+            Variant::Atomic => {
+                if start_piece.kind == King && self.is_capture(action) {
+                    return Err(MoveError::InvalidAction);
+                }
+                self.make_move(action);
+                let own_king = self.get_location_from_piece(Piece::new(King, start_piece.color));
+                let enemy_king = self.get_location_from_piece(Piece::new(King, self.turn));
+                let legal = match (own_king, enemy_king) {
+                    (None, _) => false,
+                    (_, None) => true,
+                    _ => !self.is_check(start_piece.color),
+                };
+                self.unmake_move();
+                return if legal {
+                    Ok(())
+                } else {
+                    Err(MoveError::RemainsInCheck)
+                };
+            }
+            _ => {}
+        }
 
-        self.make_move(action);
+        let remains_in_check = match self.is_king_attacked_after(action) {
+            Some(attacked) => attacked,
+            None => {
+                self.make_move(action);
+                let remains_in_check = self.is_check(start_piece.color);
+                self.unmake_move();
+                remains_in_check
+            }
+        };
 
-        if self.is_check(self.turn) {
-            self.position = current_position;
+        if remains_in_check {
             return Err(MoveError::RemainsInCheck);
         }
-        self.position = current_position;
-
         Ok(())
     }
 
+    /// Whether the mover's king is attacked once `action` is played, found
+    /// by moving the pieces on a copy of the bitboards instead of making the
+    /// move. `None` if the board keeps no bitboards.
+    fn is_king_attacked_after(&self, action: Action) -> Option<bool> {
+        let mut bitboards = *self.get_bitboards()?;
+        let Action { start, end, kind } = action;
+        let piece = match kind {
+            Drop(dkind) => Piece::new(dkind, self.turn),
+            _ => self.get_piece_from_location(start)?,
+        };
+        if let Some(captured) = self.get_piece_from_location(end) {
+            bitboards.clear(captured, end);
+        }
+        bitboards.clear(piece, start);
+        match kind {
+            EnPassant => {
+                let captured_location = Location {
+                    row: start.row,
+                    col: end.col,
+                };
+                if let Some(captured) = self.get_piece_from_location(captured_location) {
+                    bitboards.clear(captured, captured_location);
+                }
+                bitboards.set(piece, end);
+            }
+            Castling(ckind) => {
+                let (_, rook_col) = get_castled_cols(self.size, ckind);
+                let rook_location = self.get_castling_rook(piece.color, ckind)?;
+                if let Some(rook) = self.get_piece_from_location(rook_location) {
+                    bitboards.clear(rook, rook_location);
+                    let new_rook_location = Location {
+                        row: start.row,
+                        col: rook_col,
+                    };
+                    bitboards.set(rook, new_rook_location);
+                }
+                bitboards.set(piece, end);
+            }
+            Promotion(pkind) => bitboards.set(Piece::new(pkind, piece.color), end),
+            _ => bitboards.set(piece, end),
+        }
+        let king = bitboards.get_pieces(King, piece.color);
+        if king == 0 {
+            return Some(false);
+        }
+        let square = 63 - king.leading_zeros() as usize;
+        Some(bitboards.get_attackers(square, opposite_color(piece.color)) != 0)
+    }
     pub fn is_valid_translation(&self, action: Action) -> bool {
         let Action { start, end, kind } = action;
 
         let piece = self.get_piece_from_location(start);
-        if piece == None {
+        if piece.is_none() {
             return false;
         };
         let piece = piece.unwrap();
@@ -574,12 +1433,17 @@ impl Board {
         let dy = end.row as i8 - start.row as i8;
 
         match piece.kind {
-            Pawn => is_valid_pawn_translation(size, start.row, dx, dy, color),
+            Pawn => is_valid_pawn_translation(self, start.row, dx, dy, color),
             Rook => is_valid_rook_translation(dx, dy),
             Knight => is_valid_knight_translation(dx, dy),
             Bishop => is_valid_bishop_translation(dx, dy),
             Queen => is_valid_queen_translation(dx, dy),
             King => is_valid_king_translation(dx, dy),
+            Archbishop => is_valid_archbishop_translation(dx, dy),
+            Chancellor => is_valid_chancellor_translation(dx, dy),
+            Amazon => is_valid_amazon_translation(dx, dy),
+            Camel => is_valid_camel_translation(dx, dy),
+            Grasshopper => is_valid_grasshopper_translation(dx, dy),
         }
     }
     pub fn is_valid_capture(&self, action: Action) -> bool {
@@ -589,7 +1453,7 @@ impl Board {
         };
 
         let piece = self.get_piece_from_location(start);
-        if piece == None {
+        if piece.is_none() {
             return false;
         };
         let piece = piece.unwrap();
@@ -629,10 +1493,8 @@ impl Board {
 
             let piece = self.get_piece_from_location(location);
 
-            if piece != None {
-                if !(col == end.col as i8 && row == end.row as i8) {
-                    return true;
-                }
+            if piece.is_some() && !(col == end.col as i8 && row == end.row as i8) {
+                return true;
             }
 
             col += sx;
@@ -640,6 +1502,22 @@ impl Board {
         }
         false
     }
+    /// Whether the piece on `start` can get past the squares before `end`.
+    /// Most pieces need them empty, a grasshopper needs exactly one piece to
+    /// hop over, right in front of `end`.
+    pub fn is_path_open(&self, start: Location, end: Location) -> bool {
+        let piece = self.get_piece_from_location(start);
+        if !piece.is_some_and(|piece| piece.kind == Grasshopper) {
+            return !self.is_path_blocked(start, end);
+        }
+        let hurdle = Location {
+            row: (end.row as isize - (end.row as isize - start.row as isize).signum()) as usize,
+            col: (end.col as isize - (end.col as isize - start.col as isize).signum()) as usize,
+        };
+        hurdle != start
+            && self.get_piece_from_location(hurdle).is_some()
+            && !self.is_path_blocked(start, hurdle)
+    }
     pub fn is_end_blocked(&self, start: Location, end: Location) -> bool {
         let start_piece = self.get_piece_from_location(start).unwrap();
         if let Some(piece) = self.get_piece_from_location(end) {
@@ -654,10 +1532,13 @@ impl Board {
     }
     pub fn is_valid_action(&self, action: Action) -> bool {
         let Action { start, end, kind } = action;
+        if let Drop(kind) = kind {
+            return start == end && self.is_valid_drop(kind, end);
+        }
 
         let end_piece = self.get_piece_from_location(end);
         let start_piece = self.get_piece_from_location(start);
-        if start_piece == None {
+        if start_piece.is_none() {
             return false;
         };
         if start_piece.unwrap().color != self.turn {
@@ -666,13 +1547,13 @@ impl Board {
 
         match kind {
             Normal => {
-                if end_piece != None {
+                if end_piece.is_some() {
                     return false;
                 }
                 if !self.is_valid_translation(action) {
                     return false;
                 }
-                if self.is_path_blocked(start, end) {
+                if !self.is_path_open(start, end) {
                     return false;
                 }
                 if self.is_end_blocked(start, end) {
@@ -688,135 +1569,127 @@ impl Board {
                 if !self.is_valid_capture(action) {
                     return false;
                 }
-                if self.is_path_blocked(start, end) {
+                if !self.is_path_open(start, end) {
                     return false;
                 }
             }
             Castling(kind) => {
-                let direction: i8 = if self.turn == First { -1 } else { 1 };
-                let home_row = if direction == 1 { 0 } else { self.size - 1 };
-                let rook_col = if kind == Long { 0 } else { self.size - 1 };
-                if ![2, 6].contains(&end.col) {
-                    return false;
-                };
-                if start.row != home_row || end.row != home_row {
-                    return false;
-                }
-
-                let king_location = Location {
-                    row: home_row,
-                    col: 4,
-                };
-                let king = self.get_piece_from_location(king_location);
-                if let Some(king) = king {
-                    if king.kind != King || king.moved == true {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
-                let rook_location = Location {
-                    row: home_row,
-                    col: rook_col,
-                };
-                let rook = self.get_piece_from_location(rook_location);
-                if let Some(rook) = rook {
-                    if rook.kind != Rook || rook.moved == true {
-                        return false;
-                    }
-                } else {
-                    return false;
-                }
-
-                let start = if kind == Long { 1 } else { self.size - 3 };
-                let len = if kind == Long { 2 } else { 1 };
-                for i in start..=start + len {
-                    let location = Location {
-                        row: home_row,
-                        col: i,
-                    };
-                    if self.get_piece_from_location(location) != None
-                        || (self.is_square_attacked(location, opposite_color(self.turn)) && i != 1)
-                    {
-                        return false;
-                    }
-                }
-                return true;
+                return self.get_castling_action(kind) == Some(action) && self.can_castle(kind);
+            }
+            EnPassant => return is_valid_en_passant(self, start, end, self.turn),
+            Promotion(kind) => {
+                return self.rules.promotion_kinds.contains(&kind)
+                    && is_valid_promotion(self, start, end, self.turn)
             }
-            EnPassant => return is_valid_en_passant(self, self.size, start, end, self.turn),
-            Promotion(_) => return is_valid_promotion(self, start, end, self.turn),
+            Drop(_) => unreachable!(),
         }
 
         true
     }
 
     pub fn get_valid_actions(&mut self, start: Location) -> Vec<Action> {
-        let mut actions = Vec::new();
-        for row in 0..self.position.len() {
-            for col in 0..self.position[row].len() {
-                let end = Location { row, col };
-                let action = self.get_action_from_locations(start, end);
-                if let Ok(_) = self.test_move(action) {
-                    actions.push(action);
-                }
-            }
-        }
-        actions
+        self.generate_actions(Some(start))
     }
     pub fn get_all_valid_actions(&mut self) -> Vec<Action> {
-        let mut actions = Vec::new();
-        for row in 0..self.size {
-            for col in 0..self.size {
-                let location = Location { row, col };
-                if let Some(piece) = self.get_piece_from_location(location) {
-                    if piece.color != self.turn {
-                        continue;
-                    }
-                    actions.extend(self.get_valid_actions(location));
-                }
-            }
-        }
-        actions
+        self.generate_actions(None)
     }
     pub fn get_random_action(&mut self) -> Action {
         let actions = self.get_all_valid_actions();
         *actions.choose(&mut rand::thread_rng()).unwrap()
     }
+    /// Infers the kind of the action from `start` to `end`. A pawn reaching
+    /// the last row promotes to a queen unless the caller picks another piece.
     pub fn get_action_from_locations(&self, start: Location, end: Location) -> Action {
         let piece = self.get_piece_from_location(start);
         let end_piece = self.get_piece_from_location(end);
         let mut kind = Normal;
 
         if let Some(piece) = piece {
-            if piece.kind == King && end.col.abs_diff(start.col) > 1 {
-                if end.col as isize - start.col as isize > 0 {
-                    kind = Castling(Short);
-                } else {
-                    kind = Castling(Long);
-                }
+            let castling = if end.col > start.col { Short } else { Long };
+            if piece.kind == King && self.get_castling_rook(piece.color, castling) == Some(end) {
+                // Moving the king onto its own rook castles, as in Chess960.
+                return self.get_castling_action(castling).unwrap_or(Action {
+                    start,
+                    end,
+                    kind: Castling(castling),
+                });
+            } else if piece.kind == King && !self.chess960 && end.col.abs_diff(start.col) > 1 {
+                kind = Castling(castling);
             } else if piece.kind == Pawn {
                 let direction: i8 = if piece.color == First { -1 } else { 1 };
                 let last_row = if direction == 1 { self.size - 1 } else { 0 };
-                let neighbor_location = Location {
-                    row: start.row,
-                    col: end.col,
-                };
-                let neighbor = self.get_piece_from_location(neighbor_location);
                 if end.row == last_row {
                     kind = Promotion(Queen);
-                } else if end_piece != None {
+                } else if end_piece.is_some() {
                     kind = Capture;
-                } else if neighbor != None && start.col.abs_diff(end.col) == 1 {
+                } else if self.en_passant == Some(end) && start.col.abs_diff(end.col) == 1 {
                     kind = EnPassant;
                 } else {
                     kind = Normal;
                 }
-            } else if end_piece != None && end_piece.unwrap().color != piece.color {
+            } else if end_piece.is_some() && end_piece.unwrap().color != piece.color {
                 kind = Capture
             }
         }
         Action { start, end, kind }
     }
+    pub fn get_action_from_uci(&self, input: &str) -> Result<Action, MoveError> {
+        let input = input.trim();
+        if !input.is_ascii() || input.len() < 4 || input.len() > 7 {
+            return Err(MoveError::InvalidLocationStringLength);
+        }
+        // Drops name the piece and the square, as in `N@f3`.
+        if let Some((letter, square)) = input.split_once('@') {
+            let kind = match letter.chars().next().and_then(get_piece_kind_from_letter) {
+                Some(kind) if letter.len() == 1 => kind,
+                _ => return Err(MoveError::InvalidAction),
+            };
+            let end = algebraic_to_location(square, self.size)?;
+            return Ok(Action {
+                start: end,
+                end,
+                kind: Drop(kind),
+            });
+        }
+        // Ranks may have two digits, so each square runs up to the next letter.
+        let end_start = 1 + input[1..]
+            .find(|c: char| c.is_ascii_alphabetic())
+            .ok_or(MoveError::InvalidLocationString)?;
+        let end_length = 1 + input[end_start + 1..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        let start = algebraic_to_location(&input[..end_start], self.size)?;
+        let end = algebraic_to_location(&input[end_start..end_start + end_length], self.size)?;
+        let mut action = self.get_action_from_locations(start, end);
+        let mut rest = input[end_start + end_length..].chars();
+        if rest.clone().count() > 1 {
+            return Err(MoveError::InvalidAction);
+        }
+        if let Some(letter) = rest.next() {
+            match (get_piece_kind_from_letter(letter), action.kind) {
+                (Some(kind), Promotion(_))
+                    if kind != Pawn
+                        && (kind != King || self.rules.promotion_kinds.contains(&King)) =>
+                {
+                    action.kind = Promotion(kind)
+                }
+                _ => return Err(MoveError::InvalidAction),
+            }
+        }
+        Ok(action)
+    }
+    /// Formats an action of the side to move for UCI. In Chess960 castling is
+    /// written as the king taking its own rook.
+    pub fn get_uci_from_action(&self, action: Action) -> String {
+        let mut action = action;
+        if let (true, Castling(kind)) = (self.chess960, action.kind) {
+            if let Some(rook) = self.get_castling_rook(self.turn, kind) {
+                action.end = rook;
+            }
+        }
+        action_to_uci(action, self.size)
+    }
     pub fn get_material(&self, color: PieceColor) -> f64 {
         let mut total = 0.;
         for row in 0..self.size {
@@ -828,6 +1701,9 @@ impl Board {
                 }
             }
         }
+        for kind in HAND_KINDS {
+            total += self.get_hand_count(color, kind) as f64 * get_piece_kind_worth(kind);
+        }
         total
     }
     pub fn get_material_difference(&self) -> f64 {
@@ -839,34 +1715,25 @@ impl Board {
     }
 
     pub fn is_square_attacked(&self, end: Location, color: PieceColor) -> bool {
-        for row in 0..self.size {
-            for col in 0..self.size {
-                let start = Location { row, col };
-                if let Some(piece) = self.get_piece_from_location(start) {
-                    if piece.color != color {
-                        continue;
-                    }
-                    let action = Action {
-                        start,
-                        end,
-                        kind: Capture,
-                    };
-                    if self.is_valid_capture(action)
-                        && !self.is_path_blocked(action.start, action.end)
-                    {
-                        return true;
-                    }
-                }
-            }
+        match self.get_bitboards() {
+            Some(bitboards) => bitboards.get_attackers(get_square(end), color) != 0,
+            None => !self.get_attackers(end, color, &[]).is_empty(),
         }
-        false
     }
     pub fn is_check(&self, color: PieceColor) -> bool {
+        if !self.variant.has_royal_king() {
+            return false;
+        }
         let king = Piece::new(King, color);
-        let king_location = self.get_location_from_piece(king).unwrap();
+        let king_location = match self.get_location_from_piece(king) {
+            Some(location) => location,
+            None => return false,
+        };
+        if self.variant == Variant::Atomic && self.are_kings_adjacent() {
+            return false;
+        }
 
-        if self.is_square_attacked(king_location, opposite_color(self.turn)) {
-            // println!("yes");
+        if self.is_square_attacked(king_location, opposite_color(color)) {
             return true;
         };
         false
@@ -878,28 +1745,134 @@ impl Board {
         self.is_check(self.turn) && self.is_moveless()
     }
 
+    /// Whether a pawn of the side to move stands next to the pawn that
+    /// could be taken en passant. Only then does the en passant square
+    /// tell two otherwise equal positions apart.
+    fn is_en_passant_capturable(&self) -> bool {
+        let target = match self.en_passant {
+            Some(target) => target,
+            None => return false,
+        };
+        let direction: i8 = if self.turn == First { -1 } else { 1 };
+        let row = target.row as i8 - direction;
+        [-1, 1].iter().any(|dx| {
+            let col = target.col as i8 + dx;
+            if col < 0 || col >= self.size as i8 || row < 0 || row >= self.size as i8 {
+                return false;
+            }
+            let location = Location {
+                row: row as usize,
+                col: col as usize,
+            };
+            self.get_piece_from_location(location)
+                .is_some_and(|piece| piece.kind == Pawn && piece.color == self.turn)
+        })
+    }
+    /// How many times the current position has occurred, counting this one.
+    pub fn get_repetition_count(&self) -> usize {
+        let current = match self.position_history.last() {
+            Some(key) => key,
+            None => return 1,
+        };
+        // Positions before the last capture or pawn move cannot repeat.
+        self.position_history
+            .iter()
+            .rev()
+            .take(self.halfmove_clock + 1)
+            .step_by(2)
+            .filter(|hash| *hash == current)
+            .count()
+    }
+    /// True for positions where neither side can ever mate: bare kings, a
+    /// single minor piece, or only bishops that all share a square colour.
+    pub fn is_insufficient_material(&self) -> bool {
+        // The variants win in other ways than mate, some with a lone king.
+        if self.variant != Variant::Standard {
+            return false;
+        }
+        let mut knights = 0;
+        let mut bishops = 0;
+        let mut bishop_colors = [false; 2];
+        for row in 0..self.size {
+            for col in 0..self.size {
+                if let Some(piece) = self.position[row][col] {
+                    match piece.kind {
+                        King => {}
+                        Knight => knights += 1,
+                        Bishop => {
+                            bishops += 1;
+                            bishop_colors[(row + col) % 2] = true;
+                        }
+                        _ => return false,
+                    }
+                }
+            }
+        }
+        if knights + bishops <= 1 {
+            return true;
+        }
+        let mixed_bishops = bishop_colors[0] && bishop_colors[1];
+        knights == 0 && !mixed_bishops
+    }
+    /// Returns the rule under which the game is drawn, if any. Threefold
+    /// repetition and the fifty-move rule are claims, the rest end the game
+    /// on their own.
+    pub fn get_draw(&mut self) -> Option<DrawKind> {
+        let moveless = self.is_moveless();
+        let check = self.is_check(self.turn);
+        if moveless && (check || self.variant == Variant::Antichess) {
+            return None;
+        }
+        if moveless {
+            return Some(DrawKind::Stalemate);
+        }
+        if self.is_insufficient_material() {
+            return Some(DrawKind::InsufficientMaterial);
+        }
+        let repetitions = self.get_repetition_count();
+        if repetitions >= 5 {
+            return Some(DrawKind::FivefoldRepetition);
+        }
+        if self.halfmove_clock >= 150 {
+            return Some(DrawKind::SeventyFiveMoveRule);
+        }
+        if repetitions >= 3 {
+            return Some(DrawKind::ThreefoldRepetition);
+        }
+        if self.halfmove_clock >= 100 {
+            return Some(DrawKind::FiftyMoveRule);
+        }
+        None
+    }
+    /// Cheap draw test for the search: any repetition of an earlier
+    /// position already counts, since the side that repeated could repeat
+    /// again. A mate on the last move before the fifty-move rule still
+    /// wins.
+    pub fn is_search_draw(&mut self) -> bool {
+        self.get_repetition_count() >= 2
+            || (self.halfmove_clock >= 100 && !self.is_checkmate())
+            || self.is_insufficient_material()
+    }
+
     pub fn get_next_actions_and_boards(&self) -> Vec<(Action, Board)> {
         let mut boards_and_actions = Vec::new();
         let mut board = self.clone();
         let actions = board.get_all_valid_actions();
         for action in actions.iter() {
-            let mut board = self.clone();
-            let _ = board.commit_move(*action);
-            boards_and_actions.push((*action, board));
+            board.make_move(*action);
+            boards_and_actions.push((*action, board.clone()));
+            board.unmake_move();
         }
         boards_and_actions
     }
     pub fn get_next_boards(&self) -> Vec<Board> {
-        let mut boards = Vec::new();
-        let mut board = self.clone();
-        let actions = board.get_all_valid_actions();
-        for action in actions.iter() {
-            let mut board = self.clone();
-            let _ = board.commit_move(*action);
-            boards.push(board);
-        }
-        boards
+        self.get_next_actions_and_boards()
+            .into_iter()
+            .map(|(_, board)| board)
+            .collect()
     }
+    /// The tree of every line `depth` plies deep, stopping early where the
+    /// game is over.
     pub fn get_position_tree(&self, depth: usize) -> TreeNode {
         fn build_tree(
             board: Board,
@@ -907,11 +1880,12 @@ impl Board {
             current_depth: usize,
             max_depth: usize,
         ) -> TreeNode {
-            if current_depth >= max_depth {
+            // A game the variant has decided is not played on.
+            if current_depth >= max_depth || board.get_variant_winner().is_some() {
                 return TreeNode {
                     board,
                     action,
-                    value: 0.,
+                    value: 0,
                     children: Vec::new(),
                 };
             }
@@ -926,7 +1900,7 @@ impl Board {
             TreeNode {
                 board,
                 action,
-                value: 0.,
+                value: 0,
                 children,
             }
         }
@@ -934,3 +1908,205 @@ impl Board {
         build_tree(self.clone(), self.last_action, 0, depth)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(board: &mut Board, moves: &[&str]) {
+        for input in moves {
+            let action = board.get_action_from_uci(input).unwrap();
+            board.commit_move(action).unwrap();
+        }
+    }
+
+    #[test]
+    fn fen_errors() {
+        let cases = [
+            ("", FenError::MissingPlacement),
+            ("8/8/8/8/8/8/8/8 w - - 0 1 extra", FenError::TooManyFields),
+            ("8/8/8/8/8/8/8/7X w - - 0 1", FenError::InvalidPiece('X')),
+            ("8/8/8/8/8/8/8/9 w - - 0 1", FenError::InvalidRowLength),
+            ("8/8/8/8/8/8/8/7 w - - 0 1", FenError::InvalidRowLength),
+            ("8/8/8/8/8/8/8/44k w - - 0 1", FenError::InvalidRowLength),
+            ("4k3/8/8/8/8/8/8/4K3 x - - 0 1", FenError::InvalidTurn),
+            ("4k3/8/8/8/8/8/8/4K3 w K - 0 1", FenError::InvalidCastling),
+            (
+                "4k3/8/8/8/8/8/8/R3K2R w KQx - 0 1",
+                FenError::InvalidCastling,
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/4K3 w KQ - 0 1",
+                FenError::InvalidCastling,
+            ),
+            ("4k3/8/8/8/8/8/8/4K3 w - e9 0 1", FenError::InvalidEnPassant),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - -3 1",
+                FenError::InvalidHalfmoveClock,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - x 1",
+                FenError::InvalidHalfmoveClock,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 0",
+                FenError::InvalidFullmoveNumber,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - 0 y",
+                FenError::InvalidFullmoveNumber,
+            ),
+        ];
+        for (fen, error) in cases {
+            assert_eq!(Board::from_fen(fen).err(), Some(error), "{}", fen);
+        }
+        // A board keeps its size, so a FEN for another one has the wrong
+        // number of rows.
+        let mut board = Board::new(8);
+        assert_eq!(
+            board.set_fen("4k1/8/8/8/8/4K1 w - - 0 1"),
+            Err(FenError::InvalidRowCount)
+        );
+    }
+    #[test]
+    fn fen_round_trips() {
+        let fens = [
+            START_FEN,
+            "r3k2r/pppq1ppp/2n5/3pp3/8/2N5/PPPQ1PPP/R3K2R w Kq - 4 9",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbqkbnr/pppp1ppp/8/8/3Pp3/5N2/PPP1PPPP/RNBQKB1R b KQkq d3 0 3",
+            "8/5k2/8/8/8/8/2K5/8 b - - 37 102",
+        ];
+        for fen in fens {
+            assert_eq!(Board::from_fen(fen).unwrap().get_fen(), fen);
+        }
+        // Missing fields take their defaults.
+        assert_eq!(
+            Board::from_fen("4k3/8/8/8/8/8/8/4K3").unwrap().get_fen(),
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1"
+        );
+    }
+    #[test]
+    fn unmake_move_restores_everything() {
+        let games: [(&str, &[&str]); 4] = [
+            // Castling both ways, after which the king and rooks have moved.
+            (
+                "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w KQkq - 3 10",
+                &["e1g1", "e8c8", "f1e1", "d8e8"],
+            ),
+            // En passant right after the double step.
+            (
+                "rnbqkbnr/ppp1pppp/8/4P3/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 2",
+                &["f7f5", "e5f6", "g8f6"],
+            ),
+            // A capture that promotes, taking a rook on its home square.
+            (
+                "r3k3/1P6/8/8/8/8/8/4K3 w q - 0 1",
+                &["b7a8q", "e8d7", "a8a7"],
+            ),
+            // A bishop takes a rook on its home square.
+            ("r3k2r/8/8/8/8/8/6B1/4K3 w kq - 5 20", &["g2a8", "h8h1"]),
+        ];
+        for (fen, moves) in games {
+            let mut board = Board::from_fen(fen).unwrap();
+            let mut snapshots = Vec::new();
+            for uci in moves.iter() {
+                snapshots.push((board.get_fen(), board.hash(), board.position.clone()));
+                play(&mut board, &[uci]);
+            }
+            while let Some((fen, hash, position)) = snapshots.pop() {
+                board.unmake_move();
+                assert_eq!(board.get_fen(), fen);
+                assert_eq!(board.hash(), hash, "{}", fen);
+                // The pieces keep their `moved` flags too.
+                assert_eq!(board.position, position, "{}", fen);
+            }
+            assert!(board.undo_stack.is_empty());
+        }
+    }
+    #[test]
+    fn threefold_and_fivefold_repetition() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        play(&mut board, &shuffle);
+        assert_eq!(board.get_repetition_count(), 2);
+        assert_eq!(board.get_draw(), None);
+        play(&mut board, &shuffle);
+        assert_eq!(board.get_draw(), Some(DrawKind::ThreefoldRepetition));
+        play(&mut board, &shuffle);
+        play(&mut board, &shuffle);
+        assert_eq!(board.get_draw(), Some(DrawKind::FivefoldRepetition));
+        board.unmake_move();
+        assert_eq!(board.get_repetition_count(), 4);
+    }
+    #[test]
+    fn move_rules_follow_the_halfmove_clock() {
+        let mut board = Board::from_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 99 80").unwrap();
+        assert_eq!(board.get_draw(), None);
+        play(&mut board, &["a1a2"]);
+        assert_eq!(board.get_draw(), Some(DrawKind::FiftyMoveRule));
+        board
+            .set_fen("4k3/8/8/8/8/8/4P3/R3K3 w - - 150 80")
+            .unwrap();
+        assert_eq!(board.get_draw(), Some(DrawKind::SeventyFiveMoveRule));
+        play(&mut board, &["e2e4"]);
+        assert_eq!(board.get_draw(), None);
+    }
+    #[test]
+    fn dead_positions() {
+        let dead = [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2N1K3 w - - 0 1",
+            "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+        ];
+        for fen in dead {
+            let mut board = Board::from_fen(fen).unwrap();
+            assert_eq!(
+                board.get_draw(),
+                Some(DrawKind::InsufficientMaterial),
+                "{}",
+                fen
+            );
+        }
+        let alive = [
+            "1b2k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+        ];
+        for fen in alive {
+            let board = Board::from_fen(fen).unwrap();
+            assert!(!board.is_insufficient_material(), "{}", fen);
+        }
+    }
+    #[test]
+    fn stalemate_is_a_draw_and_mate_is_not() {
+        let mut board = Board::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(board.get_draw(), Some(DrawKind::Stalemate));
+        let mut board = Board::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(board.get_draw(), None);
+        assert!(board.is_checkmate());
+    }
+    #[test]
+    fn moves_from_empty_squares_are_not_played() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        play(&mut board, &["e2e4"]);
+        let action = board.get_action_from_uci("e4e5").unwrap();
+        board.unmake_move();
+        assert!(matches!(
+            board.commit_move(action),
+            Err(MoveError::StartSquareEmpty)
+        ));
+        assert_eq!(board.get_fen(), START_FEN);
+    }
+    #[test]
+    #[should_panic]
+    fn making_a_move_from_an_empty_square_panics() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        let action = Action {
+            start: Location { row: 4, col: 4 },
+            end: Location { row: 3, col: 4 },
+            kind: Normal,
+        };
+        board.make_move(action);
+    }
+}
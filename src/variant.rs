@@ -0,0 +1,424 @@
+use crate::score::*;
+use crate::utils::PieceColor::*;
+use crate::utils::PieceKind::*;
+use crate::utils::*;
+
+/// The rule sets the board can play by. Each one keeps the orthodox moves
+/// and changes how the game is won, what a capture does or which moves
+/// are allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Captures explode, taking the capturer and every piece but the pawns
+    /// next to the capture square with them. Losing the king loses.
+    Atomic,
+    /// Captures are forced and the king is an ordinary piece. Losing every
+    /// piece, or having no move, wins.
+    Antichess,
+    /// Giving the third check wins.
+    ThreeCheck,
+    /// Bringing the king to one of the central squares wins.
+    KingOfTheHill,
+    /// Captured pieces change sides and can be dropped back on the board.
+    Crazyhouse,
+}
+
+pub const VARIANTS: [Variant; 6] = [
+    Variant::Standard,
+    Variant::Atomic,
+    Variant::Antichess,
+    Variant::ThreeCheck,
+    Variant::KingOfTheHill,
+    Variant::Crazyhouse,
+];
+
+/// In antichess a pawn may also promote to a king.
+const ANTICHESS_PROMOTION_KINDS: [PieceKind; 5] = [Queen, Rook, Bishop, Knight, King];
+
+/// How a finished game ended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Win(PieceColor),
+    Draw(DrawKind),
+}
+
+impl Variant {
+    /// The name used in the PGN `Variant` tag.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "Standard",
+            Variant::Atomic => "Atomic",
+            Variant::Antichess => "Antichess",
+            Variant::ThreeCheck => "Three-check",
+            Variant::KingOfTheHill => "King of the Hill",
+            Variant::Crazyhouse => "Crazyhouse",
+        }
+    }
+    /// The name used by the UCI_Variant option.
+    pub fn get_uci_name(&self) -> &'static str {
+        match self {
+            Variant::Standard => "chess",
+            Variant::Atomic => "atomic",
+            Variant::Antichess => "antichess",
+            Variant::ThreeCheck => "3check",
+            Variant::KingOfTheHill => "kingofthehill",
+            Variant::Crazyhouse => "crazyhouse",
+        }
+    }
+    /// Looks a variant up by its PGN or UCI name, ignoring case.
+    pub fn from_name(name: &str) -> Option<Variant> {
+        VARIANTS.into_iter().find(|variant| {
+            variant.get_name().eq_ignore_ascii_case(name)
+                || variant.get_uci_name().eq_ignore_ascii_case(name)
+        })
+    }
+    /// Whether the king can be checked and has to be kept safe.
+    pub fn has_royal_king(&self) -> bool {
+        *self != Variant::Antichess
+    }
+}
+
+/// Whether `location` is one of the central squares of King of the Hill:
+/// the middle four on even boards, the middle one on odd boards.
+pub fn is_hill(location: Location, size: usize) -> bool {
+    let center = (size - 1) / 2..=size / 2;
+    center.contains(&location.row) && center.contains(&location.col)
+}
+
+impl Board {
+    /// Switches to `variant`. Antichess has no castling and lets pawns
+    /// promote to kings; leaving it restores the rules from before, while
+    /// the castling rights stay with the position.
+    pub fn set_variant(&mut self, variant: Variant) {
+        let previous = self.variant;
+        self.variant = variant;
+        if variant == Variant::Antichess {
+            if previous != Variant::Antichess {
+                self.saved_rules = self.rules;
+            }
+            self.castling = CastlingRights::default();
+            self.rules.promotion_kinds = &ANTICHESS_PROMOTION_KINDS;
+        } else if previous == Variant::Antichess {
+            self.rules = self.saved_rules;
+        }
+        self.hash = self.compute_hash();
+        if let Some(key) = self.position_history.last_mut() {
+            *key = self.hash;
+        }
+    }
+    /// Whether `action` takes a piece, which is what sets off an atomic
+    /// explosion and what antichess forces.
+    pub fn is_capture(&self, action: Action) -> bool {
+        match action.kind {
+            ActionKind::EnPassant => true,
+            ActionKind::Castling(_) => false,
+            _ => self.get_piece_from_location(action.end).is_some(),
+        }
+    }
+    /// Whether the side to move has a capture, in which case antichess
+    /// allows nothing else. Only meant for antichess, where no action
+    /// exposes a king.
+    pub fn has_capture(&mut self) -> bool {
+        let actions = self.generate_pseudo_actions();
+        actions.iter().any(|&action| self.is_capture(action))
+    }
+    /// Removes the pieces blown up by a capture on `center`: the capturer
+    /// and every piece around it that is not a pawn.
+    pub(crate) fn explode(&mut self, center: Location, undo: &mut Undo) {
+        let mut exploded = 0;
+        for row in center.row.saturating_sub(1)..=(center.row + 1).min(self.size - 1) {
+            for col in center.col.saturating_sub(1)..=(center.col + 1).min(self.size - 1) {
+                let location = Location { row, col };
+                let piece = match self.get_piece_from_location(location) {
+                    Some(piece) => piece,
+                    None => continue,
+                };
+                if location != center && piece.kind == Pawn {
+                    continue;
+                }
+                undo.exploded[exploded] = Some((piece, location));
+                exploded += 1;
+                self.update_castling_rights(piece, location, location);
+                self.clear_piece(location);
+            }
+        }
+    }
+    /// Whether both kings stand next to each other. Neither can then be
+    /// taken in atomic, as the capture would blow up the capturer's king.
+    pub fn are_kings_adjacent(&self) -> bool {
+        let first = self.get_location_from_piece(Piece::new(King, First));
+        let second = self.get_location_from_piece(Piece::new(King, Second));
+        match (first, second) {
+            (Some(first), Some(second)) => {
+                first.row.abs_diff(second.row) <= 1 && first.col.abs_diff(second.col) <= 1
+            }
+            _ => false,
+        }
+    }
+    /// Whether `color` still has a piece on the board.
+    fn has_pieces(&self, color: PieceColor) -> bool {
+        self.position
+            .iter()
+            .flatten()
+            .flatten()
+            .any(|piece| piece.color == color)
+    }
+    /// The side that has won under the variant's own rules, regardless of
+    /// the moves left: the one whose opponent lost its king in atomic, lost
+    /// all its pieces in antichess, gave three checks or reached the hill.
+    pub fn get_variant_winner(&self) -> Option<PieceColor> {
+        let has_king = |color| {
+            self.get_location_from_piece(Piece::new(King, color))
+                .is_some()
+        };
+        let mut colors = [self.turn, opposite_color(self.turn)].into_iter();
+        match self.variant {
+            Variant::Standard | Variant::Crazyhouse => None,
+            Variant::Atomic => colors.find(|&color| !has_king(opposite_color(color))),
+            Variant::Antichess => colors.find(|&color| !self.has_pieces(color)),
+            Variant::ThreeCheck => colors.find(|&color| self.checks[color_index(color)] >= 3),
+            Variant::KingOfTheHill => colors.find(|&color| {
+                self.get_location_from_piece(Piece::new(King, color))
+                    .is_some_and(|location| is_hill(location, self.size))
+            }),
+        }
+    }
+    /// How the game has ended, if it has: a win under the variant's rules,
+    /// a side without moves, or a draw.
+    pub fn get_outcome(&mut self) -> Option<Outcome> {
+        if let Some(winner) = self.get_variant_winner() {
+            return Some(Outcome::Win(winner));
+        }
+        if self.is_moveless() {
+            if self.variant == Variant::Antichess {
+                return Some(Outcome::Win(self.turn));
+            }
+            if self.is_check(self.turn) {
+                return Some(Outcome::Win(opposite_color(self.turn)));
+            }
+        }
+        self.get_draw().map(Outcome::Draw)
+    }
+}
+
+/// The value of a game the variant has already decided `ply` plies from
+/// the root, from the point of view of the side to move.
+pub(crate) fn get_variant_value(board: &Board, ply: usize) -> Option<Score> {
+    let winner = board.get_variant_winner()?;
+    Some(if winner == board.turn {
+        mate_in(ply)
+    } else {
+        mated_in(ply)
+    })
+}
+
+/// The value of a position `ply` plies from the root where the side to
+/// move has no action: lost when checkmated, won in antichess, drawn
+/// otherwise.
+pub(crate) fn get_moveless_value(board: &Board, ply: usize) -> Score {
+    if board.variant == Variant::Antichess {
+        mate_in(ply)
+    } else if board.is_check(board.turn) {
+        mated_in(ply)
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::*;
+    use crate::eval::ClassicalEvaluator;
+    use crate::notation::*;
+    use crate::perft::perft;
+    use crate::score::MATE;
+    use crate::tt::TranspositionTable;
+    use std::sync::atomic::AtomicBool;
+
+    fn from_fen(fen: &str, variant: Variant) -> Board {
+        let mut board = Board::from_fen(fen).unwrap();
+        board.set_variant(variant);
+        board
+    }
+    fn play(board: &mut Board, uci: &str) -> Result<(), MoveError> {
+        let action = board.get_action_from_uci(uci).unwrap();
+        board.commit_move(action)
+    }
+    fn search(board: &Board, depth: usize) -> String {
+        let limits = SearchLimits {
+            depth: Some(depth),
+            ..SearchLimits::default()
+        };
+        let mut tt = TranspositionTable::new(1);
+        let info = search_iterative(
+            board,
+            &limits,
+            &mut tt,
+            &mut ClassicalEvaluator::default(),
+            &AtomicBool::new(false),
+            |_| {},
+        );
+        board.get_uci_from_action(info.unwrap().action)
+    }
+
+    #[test]
+    fn names() {
+        for variant in VARIANTS {
+            assert_eq!(Variant::from_name(variant.get_name()), Some(variant));
+            assert_eq!(Variant::from_name(variant.get_uci_name()), Some(variant));
+        }
+        assert_eq!(Variant::from_name("horde"), None);
+    }
+    #[test]
+    fn atomic_explosions() {
+        let fen = "4k3/8/8/3pnp2/4P3/3N4/8/4K3 w - - 0 1";
+        let mut board = from_fen(fen, Variant::Atomic);
+        let hash = board.hash();
+        // Both knights go up, the pawns around them survive.
+        play(&mut board, "e4e5").unwrap_err();
+        play(&mut board, "d3e5").unwrap();
+        assert_eq!(board.get_fen(), "4k3/8/8/3p1p2/4P3/8/8/4K3 b - - 0 1");
+        assert_eq!(board.hash(), board.compute_hash());
+        board.unmake_move();
+        assert_eq!(board.get_fen(), fen);
+        assert_eq!(board.hash(), hash);
+
+        // Blowing up the enemy king wins, even when left in check.
+        let mut board = from_fen("3k4/3b4/8/8/8/8/8/3RK2r w - - 0 1", Variant::Atomic);
+        play(&mut board, "d1d7").unwrap();
+        assert_eq!(board.get_outcome(), Some(Outcome::Win(First)));
+        // Kings cannot capture, nor blow up their own king.
+        let mut board = from_fen("8/8/8/8/8/8/3qQ3/3K3k w - - 0 1", Variant::Atomic);
+        play(&mut board, "d1d2").unwrap_err();
+        play(&mut board, "e2d2").unwrap_err();
+        // Touching kings cannot check each other.
+        let board = from_fen("8/8/8/8/8/3r4/3Kk3/8 w - - 0 1", Variant::Atomic);
+        assert!(!board.is_check(First));
+    }
+    #[test]
+    fn antichess_forces_captures() {
+        let mut board = from_fen(START_FEN, Variant::Antichess);
+        assert_eq!(perft(&mut board, 3), 8067);
+        play(&mut board, "e2e4").unwrap();
+        play(&mut board, "d7d5").unwrap();
+        // Only exd5 is allowed now.
+        assert_eq!(perft(&mut board, 1), 1);
+        assert!(matches!(
+            play(&mut board, "g1f3"),
+            Err(MoveError::CaptureRequired)
+        ));
+
+        // The king is an ordinary piece and may walk into attack or be taken.
+        let mut board = from_fen("8/8/8/8/8/8/1r6/K7 w - - 0 1", Variant::Antichess);
+        assert!(!board.is_check(First));
+        play(&mut board, "a1b2").unwrap();
+        assert_eq!(board.get_outcome(), Some(Outcome::Win(Second)));
+        // A side without moves wins.
+        let mut board = from_fen("8/8/8/8/8/p7/P7/8 w - - 0 1", Variant::Antichess);
+        assert_eq!(board.get_outcome(), Some(Outcome::Win(First)));
+        let mut board = from_fen("8/P7/8/8/8/8/8/k7 w - - 0 1", Variant::Antichess);
+        let action = san_to_action(&mut board, "a8=K").unwrap();
+        assert_eq!(action.kind, ActionKind::Promotion(King));
+        play(&mut board, "a7a8k").unwrap();
+        // An enemy grasshopper does not send the generator into itself.
+        let mut board = from_fen("k6g/8/8/8/8/8/8/7K w - - 0 1", Variant::Antichess);
+        assert_eq!(board.get_all_valid_actions().len(), 3);
+        play(&mut board, "h1g2").unwrap();
+        assert_eq!(perft(&mut board, 2), 24);
+    }
+    #[test]
+    fn three_check() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1";
+        let mut board = Board::from_fen(fen).unwrap();
+        assert_eq!(board.variant, Variant::ThreeCheck);
+        assert_eq!(board.checks, [2, 0]);
+        assert_eq!(board.get_fen(), fen);
+        let hash = board.hash();
+        play(&mut board, "a1a8").unwrap();
+        assert_eq!(board.get_fen(), "R3k3/8/8/8/8/8/8/4K3 b - - 0+3 1 1");
+        assert_eq!(board.hash(), board.compute_hash());
+        assert_eq!(board.get_outcome(), Some(Outcome::Win(First)));
+        board.unmake_move();
+        assert_eq!(board.hash(), hash);
+        assert_eq!(board.checks, [2, 0]);
+        assert_eq!(search(&board, 2), "a1a8");
+        assert_eq!(
+            Board::from_fen("8/8/8/8/8/8/8/8 w - - 4+3 0 1").err(),
+            Some(FenError::InvalidChecks)
+        );
+    }
+    #[test]
+    fn king_of_the_hill() {
+        assert!(is_hill(Location { row: 3, col: 4 }, 8));
+        assert!(!is_hill(Location { row: 2, col: 4 }, 8));
+        assert!(is_hill(Location { row: 2, col: 2 }, 5));
+        assert!(!is_hill(Location { row: 2, col: 3 }, 5));
+
+        let mut board = from_fen("7k/8/8/8/8/4K3/8/7r w - - 0 1", Variant::KingOfTheHill);
+        let action = board.get_action_from_uci(&search(&board, 2)).unwrap();
+        assert!(is_hill(action.end, board.size));
+        board.commit_move(action).unwrap();
+        assert_eq!(board.get_outcome(), Some(Outcome::Win(First)));
+    }
+    #[test]
+    fn searches_for_variant_wins() {
+        // Taking the bishop next to the king wins atomic at once.
+        let board = from_fen("4k3/3b4/8/8/8/8/8/3RK3 w - - 0 1", Variant::Atomic);
+        assert_eq!(search(&board, 2), "d1d7");
+        // In antichess White gives its last piece away.
+        let board = from_fen("k7/8/8/8/8/8/3r4/4R3 w - - 0 1", Variant::Antichess);
+        assert!(["e1e2", "e1d1"].contains(&search(&board, 2).as_str()));
+    }
+    #[test]
+    fn minimax_respects_variant_ends() {
+        let minimax = |board: &Board, depth| -> Vec<String> {
+            get_minimax_actions(board, depth, &mut ClassicalEvaluator::default())
+                .into_iter()
+                .map(|action| board.get_uci_from_action(action))
+                .collect()
+        };
+        let root_value = |board: &Board| {
+            let mut tree = board.get_position_tree(1);
+            update_tree(&mut tree, &mut ClassicalEvaluator::default());
+            tree.value
+        };
+        // A stalemate is a draw however much material is left.
+        let board = from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Variant::Standard);
+        assert_eq!(root_value(&board), 0);
+        assert_eq!(
+            minimax(
+                &from_fen("7k/8/5K2/8/8/8/8/6Q1 w - - 0 1", Variant::Standard),
+                2
+            ),
+            ["g1g7"]
+        );
+        // Blowing up the king wins at once, and the game stops there.
+        let board = from_fen("4k3/4p3/8/8/8/8/4Q3/4K3 w - - 0 1", Variant::Atomic);
+        assert_eq!(minimax(&board, 2), ["e2e7"]);
+        // A side without moves wins, so White must not block Black's pawn.
+        let board = from_fen("8/8/8/8/8/p7/P7/8 w - - 0 1", Variant::Antichess);
+        assert_eq!(root_value(&board), MATE);
+        let board = from_fen("8/8/8/8/p7/8/P6R/8 w - - 0 1", Variant::Antichess);
+        let actions = minimax(&board, 1);
+        assert!(!actions.is_empty() && !actions.contains(&"a2a3".to_string()));
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 1+3 0 1").unwrap();
+        assert_eq!(minimax(&board, 2), ["a1a8"]);
+        let board = from_fen("7k/8/8/8/8/4K3/8/7r w - - 0 1", Variant::KingOfTheHill);
+        let actions = minimax(&board, 2);
+        assert!(!actions.is_empty());
+        for uci in actions {
+            let action = board.get_action_from_uci(&uci).unwrap();
+            assert!(is_hill(action.end, board.size));
+        }
+        // Dropping the rook on the back row mates.
+        let board = Board::from_fen("7k/6pp/8/8/8/8/8/K7[R] w - - 0 1").unwrap();
+        assert_eq!(board.variant, Variant::Crazyhouse);
+        let actions = minimax(&board, 1);
+        assert_eq!(actions.len(), 6);
+        assert!(actions
+            .iter()
+            .all(|uci| uci.starts_with("R@") && uci.ends_with('8')));
+    }
+}
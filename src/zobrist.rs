@@ -0,0 +1,134 @@
+use crate::utils::*;
+use std::sync::OnceLock;
+
+/// Largest board side the key table covers.
+pub const MAX_SIZE: usize = 16;
+pub const PIECE_KINDS: usize = 11;
+/// Most pieces of one kind a hand can hold, one for every square.
+const MAX_HAND: usize = MAX_SIZE * MAX_SIZE;
+
+pub struct ZobristKeys {
+    pieces: Vec<u64>,
+    pub turn: u64,
+    pub castling: [[u64; 2]; 2],
+    en_passant: [u64; MAX_SIZE],
+    checks: [[u64; 3]; 2],
+    hands: Vec<u64>,
+}
+
+/// A xorshift generator with a fixed seed, so hashes are the same on every run.
+pub(crate) struct Random(pub(crate) u64);
+
+impl Random {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+impl ZobristKeys {
+    fn new() -> Self {
+        let mut random = Random(0x9E37_79B9_7F4A_7C15);
+        ZobristKeys {
+            pieces: (0..PIECE_KINDS * 2 * MAX_SIZE * MAX_SIZE)
+                .map(|_| random.next())
+                .collect(),
+            turn: random.next(),
+            castling: [
+                [random.next(), random.next()],
+                [random.next(), random.next()],
+            ],
+            en_passant: std::array::from_fn(|_| random.next()),
+            checks: std::array::from_fn(|_| std::array::from_fn(|_| random.next())),
+            hands: (0..PIECE_KINDS * 2 * MAX_HAND)
+                .map(|_| random.next())
+                .collect(),
+        }
+    }
+    pub fn piece(&self, piece: Piece, location: Location) -> u64 {
+        let square = location.row * MAX_SIZE + location.col;
+        let index = (piece.kind as usize * 2 + color_index(piece.color)) * MAX_SIZE * MAX_SIZE;
+        self.pieces[index + square]
+    }
+    pub fn en_passant(&self, col: usize) -> u64 {
+        self.en_passant[col]
+    }
+    /// The key for the side at `index` having given `checks` checks, from
+    /// one up to the three that win.
+    pub fn checks(&self, index: usize, checks: u8) -> u64 {
+        self.checks[index][checks.min(3) as usize - 1]
+    }
+    /// The key for the `count`th piece of `kind` in the hand of `color`. A
+    /// hand hashes as the keys of all its counts up to the one it holds.
+    pub fn hand(&self, color: PieceColor, kind: PieceKind, count: u8) -> u64 {
+        let index = (kind as usize * 2 + color_index(color)) * MAX_HAND;
+        self.hands[index + count as usize - 1]
+    }
+}
+
+pub fn get_keys() -> &'static ZobristKeys {
+    static KEYS: OnceLock<ZobristKeys> = OnceLock::new();
+    KEYS.get_or_init(ZobristKeys::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_incremental(board: &mut Board, depth: usize) {
+        assert_eq!(board.hash(), board.compute_hash(), "{}", board.get_fen());
+        assert_eq!(board.pawn_hash(), board.compute_pawn_hash());
+        if depth == 0 {
+            return;
+        }
+        let hash = board.hash();
+        let pawn_hash = board.pawn_hash();
+        for action in board.get_all_valid_actions() {
+            board.make_move(action);
+            check_incremental(board, depth - 1);
+            board.unmake_move();
+            assert_eq!(board.hash(), hash);
+            assert_eq!(board.pawn_hash(), pawn_hash);
+        }
+    }
+
+    #[test]
+    fn incremental_hash_matches_full_hash() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+            check_incremental(&mut board, 2);
+        }
+    }
+    #[test]
+    fn transpositions_share_a_hash() {
+        let mut first = Board::from_fen(START_FEN).unwrap();
+        let mut second = first.clone();
+        for input in ["g1f3", "g8f6", "b1c3"] {
+            first.make_move(first.get_action_from_uci(input).unwrap());
+        }
+        for input in ["b1c3", "g8f6", "g1f3"] {
+            second.make_move(second.get_action_from_uci(input).unwrap());
+        }
+        assert_eq!(first.hash(), second.hash());
+        assert_ne!(first.hash(), Board::from_fen(START_FEN).unwrap().hash());
+    }
+    #[test]
+    fn hash_covers_turn_castling_and_en_passant() {
+        let hash = |fen: &str| Board::from_fen(fen).unwrap().hash();
+        let base = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_ne!(hash(base), hash("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1"));
+        assert_ne!(hash(base), hash("r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1"));
+
+        // The en passant square only counts when it can be used.
+        let quiet = "4k3/8/8/8/4P3/8/8/4K3 b - - 0 1";
+        assert_eq!(hash(quiet), hash("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1"));
+        let capturable = "4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1";
+        assert_ne!(hash(capturable), hash("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"));
+    }
+}